@@ -0,0 +1,4 @@
+fn main() {
+    // the migrations are embedded in the binary, so new ones need a rebuild
+    println!("cargo:rerun-if-changed=migrations");
+}
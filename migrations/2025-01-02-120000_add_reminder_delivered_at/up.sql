@@ -0,0 +1,2 @@
+-- Set once a reminder has been handed to a delivery channel, so it is never sent twice
+ALTER TABLE reminders ADD COLUMN delivered_at TIMESTAMP;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE reminders DROP COLUMN call_status;
+ALTER TABLE reminders DROP COLUMN call_id;
@@ -0,0 +1,3 @@
+-- Outbound call placed for a reminder and the last status the voice platform reported for it
+ALTER TABLE reminders ADD COLUMN call_id TEXT;
+ALTER TABLE reminders ADD COLUMN call_status TEXT;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX reminders_user_id;
+ALTER TABLE reminders DROP COLUMN user_id;
+DROP TABLE users;
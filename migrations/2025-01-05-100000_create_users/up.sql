@@ -0,0 +1,10 @@
+-- Callers are identified by their phone number
+CREATE TABLE users (
+    id TEXT PRIMARY KEY NOT NULL,
+    phone_number TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL
+);
+
+-- Reminders stored before users existed keep a NULL owner and are never delivered
+ALTER TABLE reminders ADD COLUMN user_id TEXT REFERENCES users(id);
+CREATE INDEX reminders_user_id ON reminders (user_id);
@@ -0,0 +1,2 @@
+-- RRULE for repeating reminders; remind_at always holds the next occurrence
+ALTER TABLE reminders ADD COLUMN recurrence TEXT;
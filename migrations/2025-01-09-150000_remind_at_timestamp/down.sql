@@ -0,0 +1,20 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE reminders_old (
+    id TEXT PRIMARY KEY NOT NULL,
+    message TEXT NOT NULL,
+    remind_at TEXT NOT NULL,
+    delivered_at TIMESTAMP,
+    call_id TEXT,
+    call_status TEXT,
+    user_id TEXT REFERENCES users(id),
+    recurrence TEXT
+);
+
+INSERT INTO reminders_old (id, message, remind_at, delivered_at, call_id, call_status, user_id, recurrence)
+SELECT id, message, strftime('%Y-%m-%dT%H:%M:%S+00:00', remind_at), delivered_at, call_id, call_status, user_id, recurrence
+FROM reminders;
+
+DROP TABLE reminders;
+ALTER TABLE reminders_old RENAME TO reminders;
+
+CREATE INDEX reminders_user_id ON reminders (user_id);
@@ -0,0 +1,24 @@
+-- Store remind_at as a UTC timestamp instead of whatever string the assistant sent.
+-- SQLite can't change a column's type, so the table is rebuilt. Rows whose remind_at
+-- can't be read as a date could never fire and are dropped.
+CREATE TABLE reminders_new (
+    id TEXT PRIMARY KEY NOT NULL,
+    message TEXT NOT NULL,
+    remind_at TIMESTAMP NOT NULL,
+    delivered_at TIMESTAMP,
+    call_id TEXT,
+    call_status TEXT,
+    user_id TEXT REFERENCES users(id),
+    recurrence TEXT
+);
+
+INSERT INTO reminders_new (id, message, remind_at, delivered_at, call_id, call_status, user_id, recurrence)
+SELECT id, message, datetime(remind_at), delivered_at, call_id, call_status, user_id, recurrence
+FROM reminders
+WHERE datetime(remind_at) IS NOT NULL;
+
+DROP TABLE reminders;
+ALTER TABLE reminders_new RENAME TO reminders;
+
+CREATE INDEX reminders_user_id ON reminders (user_id);
+CREATE INDEX reminders_pending ON reminders (remind_at) WHERE delivered_at IS NULL;
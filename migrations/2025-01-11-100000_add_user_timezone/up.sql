@@ -0,0 +1,2 @@
+-- IANA zone the caller speaks times in; NULL means the server default
+ALTER TABLE users ADD COLUMN timezone TEXT;
@@ -0,0 +1,2 @@
+-- Name of the answer provider for this caller; NULL means the deployment default
+ALTER TABLE users ADD COLUMN answer_provider TEXT;
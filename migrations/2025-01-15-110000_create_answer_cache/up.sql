@@ -0,0 +1,10 @@
+-- Answers to recent questions, so repeats don't wait on a provider
+CREATE TABLE answer_cache (
+    question TEXT NOT NULL,
+    locale TEXT NOT NULL,
+    answer TEXT NOT NULL,
+    citations TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (question, locale)
+);
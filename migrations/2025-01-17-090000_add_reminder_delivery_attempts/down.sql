@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE reminders DROP COLUMN retry_at;
+ALTER TABLE reminders DROP COLUMN delivery_attempts;
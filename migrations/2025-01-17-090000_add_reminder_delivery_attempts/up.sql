@@ -0,0 +1,3 @@
+-- Failed deliveries of the pending occurrence, and when the next try is due
+ALTER TABLE reminders ADD COLUMN delivery_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE reminders ADD COLUMN retry_at TIMESTAMP;
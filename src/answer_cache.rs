@@ -0,0 +1,141 @@
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use crate::answers::Answer;
+use crate::models::CachedAnswer;
+
+
+/// Questions whose answer changes by the minute.
+const LIVE_WORDS: &[&str] = &[
+    "now", "current", "currently", "latest", "live", "news", "score", "scores", "price", "prices",
+    "stock", "stocks", "traffic", "delayed", "delay",
+];
+/// Questions whose answer holds for the rest of the day.
+const DAILY_WORDS: &[&str] = &[
+    "today", "tonight", "tomorrow", "weather", "forecast", "temperature", "rain", "snow", "open",
+    "opens", "close", "closes", "closing", "hours", "schedule", "timetable", "week", "weekend",
+];
+
+const LIVE_TTL: Duration = Duration::minutes(10);
+const DAILY_TTL: Duration = Duration::hours(1);
+const STABLE_TTL: Duration = Duration::days(30);
+
+/// Lowercases and drops punctuation and extra spaces, so "What's the weather?" and
+/// "whats the weather" share an entry.
+pub fn normalize_question(question: &str) -> String {
+    question
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// How long an answer stays fresh: minutes for news and prices, an hour for the
+/// weather and opening hours, a month for everything encyclopedic.
+pub fn time_to_live(normalized: &str) -> Duration {
+    let has_any = |words: &[&str]| normalized.split(' ').any(|word| words.contains(&word));
+    if has_any(LIVE_WORDS) {
+        LIVE_TTL
+    } else if has_any(DAILY_WORDS) {
+        DAILY_TTL
+    } else {
+        STABLE_TTL
+    }
+}
+
+/// A fresh answer to the question, if someone in the same locale asked it recently.
+pub fn lookup(
+    conn: &mut SqliteConnection,
+    question_asked: &str,
+    asker_locale: &str,
+    now: DateTime<Utc>,
+) -> Result<Option<Answer>, diesel::result::Error> {
+    use crate::schema::answer_cache::dsl::*;
+
+    let cached = answer_cache
+        .find((normalize_question(question_asked), asker_locale))
+        .filter(expires_at.gt(now.naive_utc()))
+        .first::<CachedAnswer>(conn)
+        .optional()?;
+    Ok(cached.map(|cached| Answer {
+        text: cached.answer,
+        citations: serde_json::from_str(&cached.citations).unwrap_or_default(),
+        usage: None,
+    }))
+}
+
+/// Keeps the answer for as long as `time_to_live` allows, and clears out entries
+/// that have gone stale.
+pub fn store(
+    conn: &mut SqliteConnection,
+    question_asked: &str,
+    asker_locale: &str,
+    fresh: &Answer,
+    now: DateTime<Utc>,
+) -> Result<usize, diesel::result::Error> {
+    use crate::schema::answer_cache::dsl::*;
+
+    diesel::delete(answer_cache.filter(expires_at.le(now.naive_utc()))).execute(conn)?;
+
+    let normalized = normalize_question(question_asked);
+    let entry = CachedAnswer {
+        expires_at: (now + time_to_live(&normalized)).naive_utc(),
+        question: normalized,
+        locale: asker_locale.to_string(),
+        answer: fresh.text.clone(),
+        citations: serde_json::to_string(&fresh.citations).unwrap_or_else(|_| "[]".to_string()),
+        created_at: now.naive_utc(),
+    };
+    diesel::replace_into(answer_cache).values(&entry).execute(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn answer(text: &str) -> Answer {
+        Answer {
+            text: text.to_string(),
+            citations: vec!["https://example.com".to_string()],
+            usage: None,
+        }
+    }
+
+    #[test]
+    fn time_sensitive_questions_expire_sooner() {
+        assert_eq!(time_to_live(&normalize_question("What's the latest news?")), LIVE_TTL);
+        assert_eq!(time_to_live(&normalize_question("When does the pharmacy close?")), DAILY_TTL);
+        assert_eq!(time_to_live(&normalize_question("Weather in Helsinki")), DAILY_TTL);
+        assert_eq!(time_to_live(&normalize_question("Who wrote Kalevala?")), STABLE_TTL);
+    }
+
+    #[test]
+    fn hits_until_the_entry_expires() {
+        let mut conn = crate::test_connection();
+        let now = Utc.with_ymd_and_hms(2025, 1, 15, 12, 0, 0).unwrap();
+        store(&mut conn, "What's the weather?", "Europe/Helsinki", &answer("Sunny."), now).unwrap();
+
+        let hit = lookup(&mut conn, "whats  the WEATHER", "Europe/Helsinki", now + Duration::minutes(59)).unwrap();
+        assert_eq!(hit, Some(answer("Sunny.")));
+        assert_eq!(lookup(&mut conn, "What's the weather?", "America/New_York", now).unwrap(), None);
+        assert_eq!(lookup(&mut conn, "What's the weather?", "Europe/Helsinki", now + DAILY_TTL).unwrap(), None);
+    }
+
+    #[test]
+    fn storing_replaces_and_prunes() {
+        use crate::schema::answer_cache::dsl::answer_cache;
+
+        let mut conn = crate::test_connection();
+        let now = Utc.with_ymd_and_hms(2025, 1, 15, 12, 0, 0).unwrap();
+        store(&mut conn, "latest news", "Europe/Helsinki", &answer("Old news."), now).unwrap();
+        store(&mut conn, "latest news", "Europe/Helsinki", &answer("News."), now).unwrap();
+        assert_eq!(lookup(&mut conn, "latest news", "Europe/Helsinki", now).unwrap(), Some(answer("News.")));
+
+        let later = now + Duration::hours(2);
+        store(&mut conn, "who wrote kalevala", "Europe/Helsinki", &answer("Lönnrot."), later).unwrap();
+        assert_eq!(answer_cache.count().get_result::<i64>(&mut conn).unwrap(), 1);
+    }
+}
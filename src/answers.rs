@@ -0,0 +1,749 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::config::Config;
+use crate::models::User;
+use crate::perplexity::Perplexity;
+
+
+pub const DEFAULT_SYSTEM_PROMPT: &str = "Be precise and concise.";
+pub const DEFAULT_OPENAI_MODEL: &str = "gpt-4o-mini";
+
+/// Most retries per provider, whatever is configured; the caller is waiting on the line.
+pub const MAX_RETRIES: u32 = 5;
+/// Longest wait before a retry, however many attempts have failed.
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Part of the tool call deadline kept back from answering, for the answer cache
+/// and the reply itself.
+pub const DEADLINE_HEADROOM: Duration = Duration::from_millis(500);
+
+/// What the caller hears when no provider could answer.
+pub const APOLOGY: &str = "Sorry, I couldn't find an answer right now. Please try again in a little while.";
+
+/// Something that can answer a caller's question, usually by searching the web.
+#[async_trait]
+pub trait AnswerProvider: Send + Sync {
+    /// Name used to pick the provider in configuration, e.g. `perplexity`.
+    fn name(&self) -> &str;
+    async fn ask(&self, question: &str) -> Result<Answer, AnswerError>;
+}
+
+/// An answer ready to be read out, with what came along with it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Answer {
+    /// Plain sentences without markdown or citation markers.
+    pub text: String,
+    /// Source URLs, in the order the `[n]` markers referred to them.
+    pub citations: Vec<String>,
+    pub usage: Option<Usage>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Usage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnswerError {
+    /// The provider is down, overloaded or unreachable; asking again may work.
+    Unavailable(String),
+    /// The provider refused or sent something unusable; asking again won't help.
+    Rejected(String),
+    TimedOut(Duration),
+    /// No provider is configured.
+    NoProvider,
+}
+
+impl AnswerError {
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, AnswerError::Unavailable(_) | AnswerError::TimedOut(_))
+    }
+
+    fn from_request(e: reqwest::Error) -> Self {
+        if e.is_timeout() || e.is_connect() {
+            AnswerError::Unavailable(e.to_string())
+        } else {
+            AnswerError::Rejected(e.to_string())
+        }
+    }
+}
+
+impl fmt::Display for AnswerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnswerError::Unavailable(reason) => write!(f, "answer provider unavailable: {}", reason),
+            AnswerError::Rejected(reason) => write!(f, "answer provider failed: {}", reason),
+            AnswerError::TimedOut(after) => write!(f, "answer provider timed out after {:?}", after),
+            AnswerError::NoProvider => write!(f, "no answer provider is configured"),
+        }
+    }
+}
+
+impl std::error::Error for AnswerError {}
+
+impl Answer {
+    /// The answer followed by its sources, for texting to the caller.
+    pub fn with_sources(&self) -> String {
+        if self.citations.is_empty() {
+            return self.text.clone();
+        }
+        let sources: Vec<String> = self.citations
+            .iter()
+            .enumerate()
+            .map(|(index, url)| format!("[{}] {}", index + 1, url))
+            .collect();
+        format!("{}\n\nSources:\n{}", self.text, sources.join("\n"))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatCompletion {
+    model: Option<String>,
+    choices: Vec<Choice>,
+    #[serde(default)]
+    citations: Vec<String>,
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Choice {
+    message: ChatMessage,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatMessage {
+    content: String,
+}
+
+/// Any OpenAI-compatible `/chat/completions` endpoint, such as OpenAI itself or a
+/// local llama.cpp server.
+pub struct ChatCompletions {
+    client: reqwest::Client,
+    name: String,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+    system_prompt: String,
+}
+
+impl ChatCompletions {
+    pub fn new(name: &str, base_url: &str, model: &str) -> Self {
+        ChatCompletions {
+            client: reqwest::Client::new(),
+            name: name.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: None,
+            model: model.to_string(),
+            system_prompt: DEFAULT_SYSTEM_PROMPT.to_string(),
+        }
+    }
+
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    pub fn with_system_prompt(mut self, system_prompt: &str) -> Self {
+        self.system_prompt = system_prompt.to_string();
+        self
+    }
+
+    /// The `[openai]` endpoint, when it has a `base_url`.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let openai = &config.openai;
+        let mut provider = ChatCompletions::new("openai", openai.base_url.as_deref()?, &openai.model)
+            .with_system_prompt(&config.answers.system_prompt);
+        if let Some(api_key) = &openai.api_key {
+            provider = provider.with_api_key(api_key);
+        }
+        Some(provider)
+    }
+}
+
+#[async_trait]
+impl AnswerProvider for ChatCompletions {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn ask(&self, question: &str) -> Result<Answer, AnswerError> {
+        let payload = json!({
+            "model": self.model,
+            "messages": [
+                {
+                    "role": "system",
+                    "content": self.system_prompt
+                },
+                {
+                    "role": "user",
+                    "content": question
+                }
+            ]
+        });
+
+        let mut request = self.client
+            .post(format!("{}/chat/completions", self.base_url))
+            .header("accept", "application/json")
+            .json(&payload);
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(AnswerError::from_request)?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            let reason = format!("{} returned {}: {}", self.name, status, text);
+            return Err(if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                AnswerError::Unavailable(reason)
+            } else {
+                AnswerError::Rejected(reason)
+            });
+        }
+        let completion: ChatCompletion = response
+            .json()
+            .await
+            .map_err(|e| AnswerError::Rejected(e.to_string()))?;
+
+        let content = completion.choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message.content)
+            .ok_or_else(|| AnswerError::Rejected("response had no choices".to_string()))?;
+        if let Some(usage) = &completion.usage {
+            tracing::info!(
+                "{} {} used {} prompt and {} completion tokens",
+                self.name,
+                completion.model.as_deref().unwrap_or(&self.model),
+                usage.prompt_tokens,
+                usage.completion_tokens
+            );
+        }
+
+        Ok(Answer {
+            text: speakable(&content),
+            citations: completion.citations,
+            usage: completion.usage,
+        })
+    }
+}
+
+/// How long to wait for a provider and how often to ask it again before moving on
+/// to the next one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub timeout: Duration,
+    /// Attempts after the first one, per provider, at most `MAX_RETRIES`.
+    pub retries: u32,
+    /// Wait before the first retry, doubled for every one after it up to
+    /// `MAX_RETRY_DELAY`.
+    pub backoff: Duration,
+    /// Longest a question may take over every retry and fallback, so the caller
+    /// hears an apology rather than nothing when the providers hang.
+    pub budget: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            timeout: Duration::from_secs(6),
+            retries: 1,
+            backoff: Duration::from_millis(250),
+            budget: Duration::from_millis(14_500),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The timeout, retries and backoff from `[answers]`, within what
+    /// `server.tool_deadline_ms` leaves for answering.
+    pub fn from_config(config: &Config) -> Self {
+        RetryPolicy {
+            timeout: Duration::from_millis(config.answers.timeout_ms),
+            retries: config.answers.retries,
+            backoff: Duration::from_millis(config.answers.backoff_ms),
+            budget: Duration::from_millis(config.server.tool_deadline_ms).saturating_sub(DEADLINE_HEADROOM),
+        }
+    }
+
+    /// How long one provider can take when every attempt times out.
+    pub fn longest_attempt(&self) -> Duration {
+        let waits: Duration = (1..=self.max_retries()).map(|retry| self.delay_before(retry)).sum();
+        self.timeout.saturating_mul(self.max_retries() + 1).saturating_add(waits)
+    }
+
+    fn delay_before(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(MAX_RETRY_DELAY)
+    }
+
+    fn max_retries(&self) -> u32 {
+        self.retries.min(MAX_RETRIES)
+    }
+}
+
+/// The configured providers in fallback order. Each user can be pointed at one of
+/// them by name, and everyone else starts with the deployment default.
+#[derive(Clone, Default)]
+pub struct AnswerProviders {
+    providers: Vec<Arc<dyn AnswerProvider>>,
+    default: Option<String>,
+    policy: RetryPolicy,
+}
+
+impl AnswerProviders {
+    pub fn new(providers: Vec<Arc<dyn AnswerProvider>>) -> Self {
+        AnswerProviders {
+            providers,
+            default: None,
+            policy: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Makes `name` the default instead of the first provider.
+    pub fn with_default(mut self, name: &str) -> Self {
+        self.default = Some(name.to_string());
+        self
+    }
+
+    /// Registers every provider that has its settings, in the fallback order given
+    /// by `answers.fallbacks` (e.g. `["perplexity", "openai"]`), and picks the
+    /// default from `answers.provider`.
+    pub fn from_config(config: &Config) -> Self {
+        let mut providers: Vec<Arc<dyn AnswerProvider>> = Vec::new();
+        if let Some(perplexity) = Perplexity::from_config(config) {
+            providers.push(Arc::new(perplexity));
+        }
+        if let Some(openai) = ChatCompletions::from_config(config) {
+            providers.push(Arc::new(openai));
+        }
+        if let Some(order) = &config.answers.fallbacks {
+            providers.retain(|provider| order.iter().any(|name| name == provider.name()));
+            providers.sort_by_key(|provider| order.iter().position(|name| name == provider.name()));
+        }
+        let registry = AnswerProviders::new(providers).with_policy(RetryPolicy::from_config(config));
+        match &config.answers.provider {
+            Some(name) => registry.with_default(name),
+            None => registry,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn AnswerProvider>> {
+        self.providers.iter().find(|provider| provider.name() == name).cloned()
+    }
+
+    pub fn default_provider(&self) -> Option<Arc<dyn AnswerProvider>> {
+        match &self.default {
+            Some(name) => self.get(name),
+            None => self.providers.first().cloned(),
+        }
+    }
+
+    /// The user's chosen provider, or the default when they have none or it is
+    /// no longer configured.
+    pub fn for_user(&self, user: Option<&User>) -> Option<Arc<dyn AnswerProvider>> {
+        user.and_then(|user| user.answer_provider.as_deref())
+            .and_then(|name| self.get(name))
+            .or_else(|| self.default_provider())
+    }
+
+    /// Every provider in the order they are tried for this user: their own choice,
+    /// then the default, then the rest.
+    pub fn chain_for_user(&self, user: Option<&User>) -> Vec<Arc<dyn AnswerProvider>> {
+        let mut chain: Vec<Arc<dyn AnswerProvider>> = Vec::new();
+        let preferred = [self.for_user(user), self.default_provider()];
+        for provider in preferred.into_iter().flatten().chain(self.providers.iter().cloned()) {
+            if !chain.iter().any(|added| added.name() == provider.name()) {
+                chain.push(provider);
+            }
+        }
+        chain
+    }
+
+    /// Asks down the user's chain until a provider answers, retrying the ones that
+    /// fail in a way that might pass. Returns the last error if none did, or
+    /// `TimedOut` once the policy's budget is spent.
+    pub async fn ask(&self, user: Option<&User>, question: &str) -> Result<Answer, AnswerError> {
+        tokio::time::timeout(self.policy.budget, self.ask_down_the_chain(user, question))
+            .await
+            .unwrap_or(Err(AnswerError::TimedOut(self.policy.budget)))
+    }
+
+    async fn ask_down_the_chain(&self, user: Option<&User>, question: &str) -> Result<Answer, AnswerError> {
+        let mut last_error = AnswerError::NoProvider;
+        for provider in self.chain_for_user(user) {
+            match self.ask_with_retries(provider.as_ref(), question).await {
+                Ok(answer) => return Ok(answer),
+                Err(e) => {
+                    tracing::warn!("{} could not answer, falling back: {}", provider.name(), e);
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn ask_with_retries(&self, provider: &dyn AnswerProvider, question: &str) -> Result<Answer, AnswerError> {
+        let mut retry = 0;
+        loop {
+            let result = tokio::time::timeout(self.policy.timeout, provider.ask(question))
+                .await
+                .unwrap_or(Err(AnswerError::TimedOut(self.policy.timeout)));
+            match result {
+                Err(e) if e.is_retryable() && retry < self.policy.max_retries() => {
+                    retry += 1;
+                    let delay = self.policy.delay_before(retry);
+                    tracing::info!("{} failed ({}), retry {} in {:?}", provider.name(), e, retry, delay);
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Turns a markdown answer into plain sentences a voice can read: headings, list
+/// bullets, emphasis and code marks go, links keep their text and `[1]` citation
+/// markers are dropped.
+pub fn speakable(markdown: &str) -> String {
+    let sentences: Vec<String> = markdown
+        .lines()
+        .map(|line| strip_inline(strip_line_marker(line.trim())))
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            if line.ends_with(['.', '!', '?', ':', ';', ',']) {
+                line
+            } else {
+                format!("{}.", line)
+            }
+        })
+        .collect();
+    sentences.join(" ")
+}
+
+/// Drops a heading's `#`s or a list item's bullet.
+fn strip_line_marker(line: &str) -> &str {
+    let line = line.trim_start_matches('#').trim_start();
+    ["- ", "* ", "+ ", "• "]
+        .iter()
+        .find_map(|bullet| line.strip_prefix(bullet))
+        .unwrap_or(line)
+}
+
+fn strip_inline(line: &str) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' | '`' => i += 1,
+            '_' if chars.get(i + 1) == Some(&'_') => i += 2,
+            '[' => match closing_bracket(&chars, i) {
+                Some(close) if is_citation(&chars[i + 1..close]) => {
+                    // "Helsinki [1]." reads as "Helsinki."
+                    while out.ends_with(' ') {
+                        out.pop();
+                    }
+                    i = close + 1;
+                }
+                Some(close) if chars.get(close + 1) == Some(&'(') => {
+                    out.extend(&chars[i + 1..close]);
+                    i = chars[close..]
+                        .iter()
+                        .position(|&c| c == ')')
+                        .map_or(chars.len(), |offset| close + offset + 1);
+                }
+                _ => {
+                    out.push('[');
+                    i += 1;
+                }
+            },
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+fn closing_bracket(chars: &[char], open: usize) -> Option<usize> {
+    chars[open..].iter().position(|&c| c == ']').map(|offset| open + offset)
+}
+
+fn is_citation(inside: &[char]) -> bool {
+    inside.iter().any(char::is_ascii_digit)
+        && inside.iter().all(|c| c.is_ascii_digit() || *c == ',' || *c == ' ')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn strips_markdown_and_citation_markers() {
+        let markdown = "## Weather\n\nIt is **sunny** in Helsinki today [1][2].\n\n\
+            - High of *20* degrees\n- See [the forecast](https://example.com) for `details`[3]";
+        assert_eq!(
+            speakable(markdown),
+            "Weather. It is sunny in Helsinki today. High of 20 degrees. See the forecast for details."
+        );
+    }
+
+    #[test]
+    fn keeps_ordinary_brackets_and_numbers() {
+        assert_eq!(speakable("Call 112 [emergency] now"), "Call 112 [emergency] now.");
+        assert_eq!(speakable("1. First\n2. Second"), "1. First. 2. Second.");
+    }
+
+    #[tokio::test]
+    async fn asks_openai_compatible_server_without_key() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_partial_json(json!({
+                "model": "llama-3",
+                "messages": [{ "role": "system", "content": "Answer in Finnish." }, { "role": "user" }]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{ "message": { "role": "assistant", "content": "Helsinki." } }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let local = ChatCompletions::new("local", &format!("{}/v1/", server.uri()), "llama-3")
+            .with_system_prompt("Answer in Finnish.");
+        let answer = local.ask("capital of Finland?").await.unwrap();
+        assert_eq!(answer.text, "Helsinki.");
+        assert!(answer.citations.is_empty());
+        assert_eq!(answer.usage, None);
+        let request = &server.received_requests().await.unwrap()[0];
+        assert!(!request.headers.contains_key("authorization"));
+    }
+
+    #[tokio::test]
+    async fn reports_errors_and_empty_responses() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "choices": [] })))
+            .mount(&server)
+            .await;
+        let provider = ChatCompletions::new("local", &server.uri(), "llama-3");
+        assert!(matches!(provider.ask("anything").await, Err(AnswerError::Rejected(_))));
+
+        server.reset().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+        assert!(matches!(provider.ask("anything").await, Err(AnswerError::Rejected(_))));
+
+        server.reset().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        assert!(matches!(provider.ask("anything").await, Err(AnswerError::Unavailable(_))));
+    }
+
+    /// Fails with the queued errors, then answers with its name.
+    struct Scripted {
+        name: &'static str,
+        failures: std::sync::Mutex<Vec<AnswerError>>,
+        delay: Duration,
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    impl Scripted {
+        fn new(name: &'static str, failures: Vec<AnswerError>) -> Arc<Self> {
+            Scripted::with_delay(name, failures, Duration::ZERO)
+        }
+
+        fn with_delay(name: &'static str, failures: Vec<AnswerError>, delay: Duration) -> Arc<Self> {
+            Arc::new(Scripted {
+                name,
+                failures: std::sync::Mutex::new(failures),
+                delay,
+                calls: Default::default(),
+            })
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl AnswerProvider for Scripted {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn ask(&self, _question: &str) -> Result<Answer, AnswerError> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            let mut failures = self.failures.lock().unwrap();
+            if !failures.is_empty() {
+                return Err(failures.remove(0));
+            }
+            Ok(Answer { text: self.name.to_string(), citations: Vec::new(), usage: None })
+        }
+    }
+
+    fn quick_policy() -> RetryPolicy {
+        RetryPolicy {
+            timeout: Duration::from_millis(100),
+            retries: 2,
+            backoff: Duration::from_millis(1),
+            budget: Duration::from_secs(5),
+        }
+    }
+
+    fn unavailable() -> AnswerError {
+        AnswerError::Unavailable("503".to_string())
+    }
+
+    #[tokio::test]
+    async fn retries_transient_failures() {
+        let flaky = Scripted::new("flaky", vec![unavailable(), unavailable()]);
+        let providers = AnswerProviders::new(vec![flaky.clone()]).with_policy(quick_policy());
+
+        assert_eq!(providers.ask(None, "question").await.unwrap().text, "flaky");
+        assert_eq!(flaky.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_and_delays_are_capped() {
+        let policy = RetryPolicy {
+            timeout: Duration::from_millis(100),
+            retries: u32::MAX,
+            backoff: Duration::from_secs(3),
+            budget: Duration::from_secs(5),
+        };
+        assert_eq!(policy.delay_before(1), Duration::from_secs(3));
+        assert_eq!(policy.delay_before(2), MAX_RETRY_DELAY);
+        assert_eq!(policy.delay_before(u32::MAX), MAX_RETRY_DELAY);
+
+        let down = Scripted::new("down", vec![unavailable(); 10]);
+        let policy = RetryPolicy { backoff: Duration::from_millis(1), ..policy };
+        let providers = AnswerProviders::new(vec![down.clone()]).with_policy(policy);
+        assert!(providers.ask(None, "question").await.is_err());
+        assert_eq!(down.calls(), MAX_RETRIES as usize + 1);
+    }
+
+    #[tokio::test]
+    async fn falls_back_when_retries_run_out_or_the_request_is_rejected() {
+        let down = Scripted::new("down", vec![unavailable(); 3]);
+        let rejecting = Scripted::new("rejecting", vec![AnswerError::Rejected("400".to_string())]);
+        let backup = Scripted::new("backup", Vec::new());
+        let providers = AnswerProviders::new(vec![down.clone(), rejecting.clone(), backup.clone()])
+            .with_policy(quick_policy());
+
+        assert_eq!(providers.ask(None, "question").await.unwrap().text, "backup");
+        assert_eq!(down.calls(), 3);
+        assert_eq!(rejecting.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn times_out_slow_providers() {
+        let slow = Scripted::with_delay("slow", Vec::new(), Duration::from_secs(5));
+        let backup = Scripted::new("backup", Vec::new());
+        let policy = RetryPolicy { retries: 0, ..quick_policy() };
+        let providers = AnswerProviders::new(vec![slow.clone(), backup]).with_policy(policy);
+
+        assert_eq!(providers.ask(None, "question").await.unwrap().text, "backup");
+        assert_eq!(slow.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_when_the_budget_is_spent() {
+        let slow = Scripted::with_delay("slow", Vec::new(), Duration::from_secs(5));
+        let slower = Scripted::with_delay("slower", Vec::new(), Duration::from_secs(5));
+        let policy = RetryPolicy { retries: 0, budget: Duration::from_millis(150), ..quick_policy() };
+        let providers = AnswerProviders::new(vec![slow, slower.clone()]).with_policy(policy);
+
+        let started = std::time::Instant::now();
+        let result = providers.ask(None, "question").await;
+        assert!(matches!(result, Err(AnswerError::TimedOut(budget)) if budget == policy.budget), "{:?}", result);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(slower.calls(), 1);
+    }
+
+    #[test]
+    fn adds_up_the_longest_attempt() {
+        assert_eq!(RetryPolicy::default().longest_attempt(), Duration::from_millis(12_250));
+        let policy = RetryPolicy { retries: 3, backoff: Duration::from_secs(2), ..RetryPolicy::default() };
+        // 4 attempts and waits of 2, 4 and 5 seconds
+        assert_eq!(policy.longest_attempt(), Duration::from_secs(35));
+    }
+
+    #[tokio::test]
+    async fn reports_the_last_error_when_everyone_fails() {
+        let providers = AnswerProviders::new(vec![
+            Scripted::new("down", vec![unavailable(); 3]),
+            Scripted::new("rejecting", vec![AnswerError::Rejected("400".to_string())]),
+        ])
+        .with_policy(quick_policy());
+
+        assert_eq!(providers.ask(None, "question").await, Err(AnswerError::Rejected("400".to_string())));
+        assert_eq!(AnswerProviders::default().ask(None, "question").await, Err(AnswerError::NoProvider));
+    }
+
+    #[test]
+    fn users_choice_leads_the_chain() {
+        let providers = AnswerProviders::new(vec![
+            Scripted::new("first", Vec::new()),
+            Scripted::new("second", Vec::new()),
+            Scripted::new("third", Vec::new()),
+        ])
+        .with_default("second");
+        let mut user = User::new("+358401234567".to_string());
+        user.answer_provider = Some("third".to_string());
+        let names = |chain: Vec<Arc<dyn AnswerProvider>>| {
+            chain.iter().map(|provider| provider.name().to_string()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(providers.chain_for_user(None)), vec!["second", "first", "third"]);
+        assert_eq!(names(providers.chain_for_user(Some(&user))), vec!["third", "second", "first"]);
+    }
+
+    #[test]
+    fn picks_the_users_provider_or_the_default() {
+        let providers = AnswerProviders::new(vec![
+            Arc::new(ChatCompletions::new("perplexity", "http://perplexity", "sonar")),
+            Arc::new(ChatCompletions::new("local", "http://localhost", "llama-3")),
+        ]);
+        let mut user = User::new("+358401234567".to_string());
+        let name = |provider: Option<Arc<dyn AnswerProvider>>| provider.map(|p| p.name().to_string());
+
+        assert_eq!(name(providers.for_user(None)), Some("perplexity".to_string()));
+        user.answer_provider = Some("local".to_string());
+        assert_eq!(name(providers.for_user(Some(&user))), Some("local".to_string()));
+        user.answer_provider = Some("removed".to_string());
+        assert_eq!(name(providers.for_user(Some(&user))), Some("perplexity".to_string()));
+
+        let providers = providers.with_default("local");
+        assert_eq!(name(providers.for_user(None)), Some("local".to_string()));
+        assert!(AnswerProviders::default().for_user(None).is_none());
+    }
+}
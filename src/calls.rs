@@ -0,0 +1,209 @@
+use async_trait::async_trait;
+use diesel::prelude::*;
+use serde::Deserialize;
+use serde_json::json;
+use crate::config::Config;
+use crate::delivery::{Delivery, DeliveryError, Receipt};
+use crate::models::{Reminder, User};
+
+
+pub const VAPI_BASE_URL: &str = "https://api.vapi.ai";
+
+/// Rings the user and has the voice assistant read the reminder out, through the
+/// voice platform's "create call" endpoint.
+pub struct CallDelivery {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    assistant_id: String,
+    phone_number_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct CreatedCall {
+    id: String,
+    status: Option<String>,
+}
+
+impl CallDelivery {
+    pub fn new(
+        base_url: &str,
+        api_key: &str,
+        assistant_id: &str,
+        phone_number_id: &str,
+    ) -> Self {
+        CallDelivery {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            assistant_id: assistant_id.to_string(),
+            phone_number_id: phone_number_id.to_string(),
+        }
+    }
+
+    /// Calls through the `[vapi]` settings, when all of them are there.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let vapi = &config.vapi;
+        Some(CallDelivery::new(
+            &vapi.base_url,
+            vapi.api_key.as_deref()?,
+            vapi.assistant_id.as_deref()?,
+            vapi.phone_number_id.as_deref()?,
+        ))
+    }
+}
+
+#[async_trait]
+impl Delivery for CallDelivery {
+    async fn deliver(&self, user: &User, reminder: &Reminder) -> Result<Receipt, DeliveryError> {
+        let payload = json!({
+            "assistantId": self.assistant_id,
+            "phoneNumberId": self.phone_number_id,
+            "customer": {
+                "number": user.phone_number
+            },
+            "assistantOverrides": {
+                "firstMessage": format!("Hi! This is your reminder: {}", reminder.message)
+            }
+        });
+
+        let response = self.client
+            .post(format!("{}/call", self.base_url))
+            .bearer_auth(&self.api_key)
+            .json(&payload)
+            .send()
+            .await
+            .map_err(|e| DeliveryError(e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(DeliveryError(format!("voice platform returned {}: {}", status, text)));
+        }
+        let call: CreatedCall = response
+            .json()
+            .await
+            .map_err(|e| DeliveryError(e.to_string()))?;
+
+        tracing::info!("Placed reminder call {} for reminder {}", call.id, reminder.id);
+        Ok(Receipt {
+            call_id: Some(call.id),
+            call_status: call.status,
+        })
+    }
+}
+
+/// Server message the voice platform sends as a call progresses.
+#[derive(Deserialize, Debug)]
+pub struct CallEventRequest {
+    pub message: CallEvent,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CallEvent {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub call: Option<CallRef>,
+    pub status: Option<String>,
+    #[serde(rename = "endedReason")]
+    pub ended_reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CallRef {
+    pub id: String,
+}
+
+impl CallEvent {
+    /// The outcome worth recording, e.g. `in-progress` or `customer-did-not-answer`.
+    pub fn outcome(&self) -> Option<&str> {
+        self.ended_reason.as_deref().or(self.status.as_deref())
+    }
+}
+
+/// Stores the latest outcome on the reminder the call was placed for.
+pub fn record_call_status(
+    conn: &mut SqliteConnection,
+    event_call_id: &str,
+    outcome: &str,
+) -> Result<usize, diesel::result::Error> {
+    use crate::schema::reminders::dsl::*;
+
+    diesel::update(reminders.filter(call_id.eq(event_call_id)))
+        .set(call_status.eq(outcome))
+        .execute(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::{Clock, Scheduler};
+    use chrono::{DateTime, TimeZone, Utc};
+    use std::sync::Arc;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    struct FixedClock;
+
+    impl Clock for FixedClock {
+        fn now(&self) -> DateTime<Utc> {
+            Utc.with_ymd_and_hms(2025, 1, 10, 12, 0, 0).unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn places_call_and_records_outcome() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/call"))
+            .and(header("authorization", "Bearer key"))
+            .and(body_partial_json(json!({
+                "assistantId": "assistant",
+                "phoneNumberId": "number",
+                "customer": { "number": "+358401234567" },
+                "assistantOverrides": { "firstMessage": "Hi! This is your reminder: take pills" }
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "id": "call-1",
+                "status": "queued"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let conn = crate::test_pool();
+        let user = crate::users::find_or_create_by_phone(&mut conn.get().unwrap(), "+358401234567").unwrap();
+        let remind_at = Utc.with_ymd_and_hms(2025, 1, 10, 8, 0, 0).unwrap();
+        let reminder = Reminder::new(user.id, "take pills".to_string(), remind_at);
+        diesel::insert_into(crate::schema::reminders::table)
+            .values(&reminder)
+            .execute(&mut *conn.get().unwrap())
+            .unwrap();
+
+        let delivery = CallDelivery::new(&server.uri(), "key", "assistant", "number");
+        let scheduler = Scheduler::new(conn.clone(), Arc::new(delivery)).with_clock(Arc::new(FixedClock));
+        assert_eq!(scheduler.tick().await.unwrap(), 1);
+
+        let load = |conn: &crate::db::Pool| {
+            crate::schema::reminders::table
+                .find(&reminder.id)
+                .first::<Reminder>(&mut *conn.get().unwrap())
+                .unwrap()
+        };
+        let stored = load(&conn);
+        assert_eq!(stored.call_id.as_deref(), Some("call-1"));
+        assert_eq!(stored.call_status.as_deref(), Some("queued"));
+
+        let event: CallEventRequest = serde_json::from_value(json!({
+            "message": {
+                "type": "end-of-call-report",
+                "call": { "id": "call-1" },
+                "endedReason": "customer-did-not-answer"
+            }
+        }))
+        .unwrap();
+        let outcome = event.message.outcome().unwrap();
+        record_call_status(&mut conn.get().unwrap(), "call-1", outcome).unwrap();
+        assert_eq!(load(&conn).call_status.as_deref(), Some("customer-did-not-answer"));
+    }
+}
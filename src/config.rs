@@ -0,0 +1,702 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use chrono_tz::Tz;
+use serde::Deserialize;
+use crate::answers::{RetryPolicy, MAX_RETRIES, MAX_RETRY_DELAY};
+use crate::tools::ToolRegistry;
+
+
+/// Read when no other file is named by `--config` or `CONFIG_FILE`, if it exists.
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Everything the server can be configured with, loaded once at startup from a
+/// TOML file and the environment, and checked before anything starts.
+///
+/// Every setting can also come from an environment variable, which wins over the
+/// file; see `config.example.toml` for the names.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub log: LogConfig,
+    pub reminders: RemindersConfig,
+    pub webhook: WebhookConfig,
+    pub tools: ToolsConfig,
+    pub answers: AnswersConfig,
+    pub perplexity: PerplexityConfig,
+    pub openai: OpenAiConfig,
+    pub twilio: TwilioConfig,
+    pub vapi: VapiConfig,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+    /// Where the voice platform reaches this server, for the tool definitions.
+    pub public_url: Option<String>,
+    /// How long a batch of tool calls may take before the stragglers are given up
+    /// on; 15 seconds keeps answers inside the voice platform's own 20 second limit.
+    pub tool_deadline_ms: u64,
+    /// Zone for times callers give without an offset, until they set their own.
+    pub default_timezone: Tz,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            public_url: None,
+            tool_deadline_ms: 15_000,
+            default_timezone: Tz::Europe__Helsinki,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// Path of the SQLite database file; required.
+    pub url: String,
+    pub pool_size: u32,
+    /// Apply pending migrations when the server starts.
+    pub migrate: bool,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            url: String::new(),
+            pool_size: 8,
+            migrate: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, for log aggregation.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("\"{}\" is not a log format, use text or json", other)),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// Which logs to keep, in `RUST_LOG` syntax, e.g. `info,dumbassistant=debug`.
+    pub filter: String,
+    pub format: LogFormat,
+    /// Headers blanked out of the request log, besides the secret-carrying ones.
+    pub redact_headers: Vec<String>,
+    /// JSON fields blanked out of the request log, besides the private ones.
+    pub redact_fields: Vec<String>,
+    /// Request bodies are cut to this many bytes in the log.
+    pub max_body: Option<usize>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            filter: "info".to_string(),
+            format: LogFormat::Text,
+            redact_headers: Vec::new(),
+            redact_fields: Vec::new(),
+            max_body: None,
+        }
+    }
+}
+
+/// How due reminders reach the user.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryChannel {
+    #[default]
+    Sms,
+    /// A phone call from the voice assistant, which needs `[vapi]`.
+    Call,
+    /// Only written to the log, for development.
+    Log,
+}
+
+impl FromStr for DeliveryChannel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sms" => Ok(DeliveryChannel::Sms),
+            "call" => Ok(DeliveryChannel::Call),
+            "log" => Ok(DeliveryChannel::Log),
+            other => Err(format!("\"{}\" is not a delivery channel, use sms, call or log", other)),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RemindersConfig {
+    pub delivery: DeliveryChannel,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// The voice platform's server secret, required in the `x-vapi-secret` header.
+    pub secret: Option<String>,
+    /// Key for HMAC signatures over the body, checked when set.
+    pub signing_secret: Option<String>,
+    /// How old a signed request may be before it counts as replayed.
+    pub tolerance_secs: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            secret: None,
+            signing_secret: None,
+            tolerance_secs: 300,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToolsConfig {
+    /// Only these tools, when given.
+    pub enabled: Option<Vec<String>>,
+    pub disabled: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnswersConfig {
+    /// Provider everyone starts with, the first configured one unless set.
+    pub provider: Option<String>,
+    /// Providers to try, in order, when the user's own fails.
+    pub fallbacks: Option<Vec<String>>,
+    pub system_prompt: String,
+    pub timeout_ms: u64,
+    /// Attempts after the first one, per provider.
+    pub retries: u32,
+    /// Wait before the first retry, doubled for every one after it.
+    pub backoff_ms: u64,
+}
+
+impl Default for AnswersConfig {
+    fn default() -> Self {
+        AnswersConfig {
+            provider: None,
+            fallbacks: None,
+            system_prompt: crate::answers::DEFAULT_SYSTEM_PROMPT.to_string(),
+            timeout_ms: 6_000,
+            retries: 1,
+            backoff_ms: 250,
+        }
+    }
+}
+
+/// Answers questions when `api_key` is set.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PerplexityConfig {
+    pub api_key: Option<String>,
+    pub model: String,
+    pub base_url: String,
+}
+
+impl Default for PerplexityConfig {
+    fn default() -> Self {
+        PerplexityConfig {
+            api_key: None,
+            model: crate::perplexity::PERPLEXITY_MODEL.to_string(),
+            base_url: crate::perplexity::PERPLEXITY_BASE_URL.to_string(),
+        }
+    }
+}
+
+/// Any OpenAI-compatible endpoint, used when `base_url` is set.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct OpenAiConfig {
+    /// e.g. `https://api.openai.com/v1` or `http://localhost:8080/v1`.
+    pub base_url: Option<String>,
+    pub api_key: Option<String>,
+    pub model: String,
+}
+
+impl Default for OpenAiConfig {
+    fn default() -> Self {
+        OpenAiConfig {
+            base_url: None,
+            api_key: None,
+            model: crate::answers::DEFAULT_OPENAI_MODEL.to_string(),
+        }
+    }
+}
+
+/// Texts through Twilio when all three credentials are set, otherwise texts are
+/// only logged.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TwilioConfig {
+    pub account_sid: Option<String>,
+    pub auth_token: Option<String>,
+    pub from_number: Option<String>,
+    pub base_url: String,
+}
+
+impl Default for TwilioConfig {
+    fn default() -> Self {
+        TwilioConfig {
+            account_sid: None,
+            auth_token: None,
+            from_number: None,
+            base_url: crate::sms::TWILIO_BASE_URL.to_string(),
+        }
+    }
+}
+
+/// The voice platform's API, for calling users with their reminders.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct VapiConfig {
+    pub api_key: Option<String>,
+    pub assistant_id: Option<String>,
+    pub phone_number_id: Option<String>,
+    pub base_url: String,
+}
+
+impl Default for VapiConfig {
+    fn default() -> Self {
+        VapiConfig {
+            api_key: None,
+            assistant_id: None,
+            phone_number_id: None,
+            base_url: crate::calls::VAPI_BASE_URL.to_string(),
+        }
+    }
+}
+
+/// Everything wrong with the configuration, so it can all be fixed in one go.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid configuration:")?;
+        for problem in &self.0 {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Environment overrides, collecting the values that don't parse.
+struct Overrides<'a> {
+    var: &'a dyn Fn(&str) -> Option<String>,
+    problems: Vec<String>,
+}
+
+impl Overrides<'_> {
+    fn get(&self, name: &str) -> Option<String> {
+        (self.var)(name).map(|value| value.trim().to_string()).filter(|value| !value.is_empty())
+    }
+
+    fn string(&self, name: &str, target: &mut String) {
+        if let Some(value) = self.get(name) {
+            *target = value;
+        }
+    }
+
+    fn optional(&self, name: &str, target: &mut Option<String>) {
+        if let Some(value) = self.get(name) {
+            *target = Some(value);
+        }
+    }
+
+    fn list(&self, name: &str) -> Option<Vec<String>> {
+        let value = self.get(name)?;
+        Some(value.split(',').map(|item| item.trim().to_string()).filter(|item| !item.is_empty()).collect())
+    }
+
+    fn parsed<T: FromStr>(&mut self, name: &str, target: &mut T)
+    where
+        T::Err: fmt::Display,
+    {
+        if let Some(value) = self.get(name) {
+            match value.parse() {
+                Ok(value) => *target = value,
+                Err(e) => self.problems.push(format!("{}: {}", name, e)),
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Loads the file at `path`, or `CONFIG_FILE`, or `config.toml` when it exists,
+    /// applies the environment (and a `.env` file) on top and checks the result.
+    pub fn load(path: Option<&Path>) -> Result<Config, ConfigError> {
+        dotenvy::dotenv().ok();
+        let path = path.map(Path::to_path_buf).or_else(|| env::var_os("CONFIG_FILE").map(PathBuf::from));
+        let config = match path {
+            Some(path) => Config::from_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Config::from_file(Path::new(DEFAULT_CONFIG_FILE))?,
+            None => Config::default(),
+        };
+        config.with_overrides(&|name| env::var(name).ok())
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| ConfigError(vec![format!("can't read {}: {}", path.display(), e)]))?;
+        Config::from_toml(&text).map_err(|ConfigError(problems)| {
+            ConfigError(problems.into_iter().map(|problem| format!("{}: {}", path.display(), problem)).collect())
+        })
+    }
+
+    pub fn from_toml(text: &str) -> Result<Config, ConfigError> {
+        toml::from_str(text).map_err(|e| ConfigError(vec![e.message().to_string()]))
+    }
+
+    /// Applies the environment variables `var` finds over these settings and checks
+    /// the result.
+    pub fn with_overrides(mut self, var: &dyn Fn(&str) -> Option<String>) -> Result<Config, ConfigError> {
+        let mut env = Overrides { var, problems: Vec::new() };
+
+        env.parsed("BIND_ADDRESS", &mut self.server.bind);
+        env.optional("PUBLIC_URL", &mut self.server.public_url);
+        env.parsed("TOOL_CALL_DEADLINE_MS", &mut self.server.tool_deadline_ms);
+        env.parsed("DEFAULT_TIMEZONE", &mut self.server.default_timezone);
+
+        env.string("DATABASE_URL", &mut self.database.url);
+        env.parsed("DATABASE_POOL_SIZE", &mut self.database.pool_size);
+        if let Some(skip) = env.get("SKIP_MIGRATIONS") {
+            self.database.migrate = matches!(skip.as_str(), "0" | "false");
+        }
+
+        env.string("RUST_LOG", &mut self.log.filter);
+        env.parsed("LOG_FORMAT", &mut self.log.format);
+        if let Some(headers) = env.list("LOG_REDACT_HEADERS") {
+            self.log.redact_headers = headers;
+        }
+        if let Some(fields) = env.list("LOG_REDACT_FIELDS") {
+            self.log.redact_fields = fields;
+        }
+        if let Some(max_body) = env.get("LOG_MAX_BODY") {
+            match max_body.parse() {
+                Ok(max_body) => self.log.max_body = Some(max_body),
+                Err(e) => env.problems.push(format!("LOG_MAX_BODY: {}", e)),
+            }
+        }
+
+        env.parsed("REMINDER_DELIVERY", &mut self.reminders.delivery);
+
+        env.optional("VAPI_SERVER_SECRET", &mut self.webhook.secret);
+        env.optional("VAPI_SIGNING_SECRET", &mut self.webhook.signing_secret);
+        env.parsed("WEBHOOK_TOLERANCE_SECS", &mut self.webhook.tolerance_secs);
+
+        if let Some(enabled) = env.list("ENABLED_TOOLS") {
+            self.tools.enabled = Some(enabled);
+        }
+        if let Some(disabled) = env.list("DISABLED_TOOLS") {
+            self.tools.disabled = disabled;
+        }
+
+        env.optional("ANSWER_PROVIDER", &mut self.answers.provider);
+        if let Some(fallbacks) = env.list("ANSWER_FALLBACKS") {
+            self.answers.fallbacks = Some(fallbacks);
+        }
+        env.string("ANSWER_SYSTEM_PROMPT", &mut self.answers.system_prompt);
+        env.parsed("ANSWER_TIMEOUT_MS", &mut self.answers.timeout_ms);
+        env.parsed("ANSWER_RETRIES", &mut self.answers.retries);
+        env.parsed("ANSWER_BACKOFF_MS", &mut self.answers.backoff_ms);
+
+        env.optional("PERPLEXITY_API_KEY", &mut self.perplexity.api_key);
+        env.string("PERPLEXITY_MODEL", &mut self.perplexity.model);
+        env.string("PERPLEXITY_BASE_URL", &mut self.perplexity.base_url);
+
+        env.optional("OPENAI_BASE_URL", &mut self.openai.base_url);
+        env.optional("OPENAI_API_KEY", &mut self.openai.api_key);
+        env.string("OPENAI_MODEL", &mut self.openai.model);
+
+        env.optional("TWILIO_ACCOUNT_SID", &mut self.twilio.account_sid);
+        env.optional("TWILIO_AUTH_TOKEN", &mut self.twilio.auth_token);
+        env.optional("TWILIO_FROM_NUMBER", &mut self.twilio.from_number);
+        env.string("TWILIO_BASE_URL", &mut self.twilio.base_url);
+
+        env.optional("VAPI_API_KEY", &mut self.vapi.api_key);
+        env.optional("VAPI_ASSISTANT_ID", &mut self.vapi.assistant_id);
+        env.optional("VAPI_PHONE_NUMBER_ID", &mut self.vapi.phone_number_id);
+        env.string("VAPI_BASE_URL", &mut self.vapi.base_url);
+
+        let mut problems = env.problems;
+        problems.extend(self.problems());
+        if problems.is_empty() {
+            Ok(self)
+        } else {
+            Err(ConfigError(problems))
+        }
+    }
+
+    /// Checks the database settings, which only the commands that open the database
+    /// need, so the others run without them.
+    pub fn check_database(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        if self.database.url.is_empty() {
+            problems.push("database.url (DATABASE_URL) is required".to_string());
+        }
+        if self.database.pool_size == 0 {
+            problems.push("database.pool_size has to be at least 1".to_string());
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError(problems))
+        }
+    }
+
+    /// Settings that parsed but don't make sense, or don't fit together. The
+    /// database settings are left to [`Config::check_database`].
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&self.log.filter) {
+            problems.push(format!("log.filter \"{}\" is not a log filter: {}", self.log.filter, e));
+        }
+        if self.server.tool_deadline_ms == 0 {
+            problems.push("server.tool_deadline_ms has to be more than 0".to_string());
+        }
+        if let Some(url) = &self.server.public_url {
+            if !(url.starts_with("https://") || url.starts_with("http://")) {
+                problems.push(format!("server.public_url \"{}\" has to start with https://", url));
+            }
+        }
+
+        let builtin = ToolRegistry::builtin();
+        let tool_names = self.tools.enabled.iter().flatten().chain(&self.tools.disabled);
+        for name in tool_names.filter(|name| builtin.get(name).is_none()) {
+            problems.push(format!("there is no tool called \"{}\" in tools", name));
+        }
+
+        let configured = self.answer_providers();
+        let provider_names = self.answers.provider.iter().chain(self.answers.fallbacks.iter().flatten());
+        for name in provider_names {
+            if !["perplexity", "openai"].contains(&name.as_str()) {
+                problems.push(format!("there is no answer provider called \"{}\", use perplexity or openai", name));
+            } else if !configured.contains(&name.as_str()) {
+                let needs = if name == "perplexity" { "perplexity.api_key" } else { "openai.base_url" };
+                problems.push(format!("answer provider \"{}\" is named but {} is not set", name, needs));
+            }
+        }
+
+        if self.answers.timeout_ms == 0 {
+            problems.push("answers.timeout_ms has to be more than 0".to_string());
+        }
+        if self.answers.retries > MAX_RETRIES {
+            problems.push(format!("answers.retries can be at most {}", MAX_RETRIES));
+        }
+        if u128::from(self.answers.backoff_ms) > MAX_RETRY_DELAY.as_millis() {
+            problems.push(format!("answers.backoff_ms can be at most {}", MAX_RETRY_DELAY.as_millis()));
+        }
+        let policy = RetryPolicy::from_config(self);
+        if self.server.tool_deadline_ms > 0 && policy.longest_attempt() > policy.budget {
+            problems.push(format!(
+                "answers.timeout_ms, retries and backoff_ms add up to {} ms, more than the {} ms server.tool_deadline_ms leaves for answering",
+                policy.longest_attempt().as_millis(),
+                policy.budget.as_millis()
+            ));
+        }
+
+        let twilio = [&self.twilio.account_sid, &self.twilio.auth_token, &self.twilio.from_number];
+        if twilio.iter().any(|value| value.is_some()) && !twilio.iter().all(|value| value.is_some()) {
+            problems.push("twilio needs all of account_sid, auth_token and from_number".to_string());
+        }
+        let vapi = [&self.vapi.api_key, &self.vapi.assistant_id, &self.vapi.phone_number_id];
+        let vapi_complete = vapi.iter().all(|value| value.is_some());
+        if vapi.iter().any(|value| value.is_some()) && !vapi_complete {
+            problems.push("vapi needs all of api_key, assistant_id and phone_number_id".to_string());
+        } else if self.reminders.delivery == DeliveryChannel::Call && !vapi_complete {
+            problems.push("reminders.delivery = \"call\" needs the [vapi] settings".to_string());
+        }
+
+        problems
+    }
+
+    /// Names of the answer providers that have their settings.
+    pub fn answer_providers(&self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        if self.perplexity.api_key.is_some() {
+            names.push("perplexity");
+        }
+        if self.openai.base_url.is_some() {
+            names.push("openai");
+        }
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn problems(result: Result<Config, ConfigError>) -> Vec<String> {
+        result.unwrap_err().0
+    }
+
+    #[test]
+    fn reads_the_file_and_lets_the_environment_win() {
+        let config = Config::from_toml(
+            r#"
+            [server]
+            bind = "127.0.0.1:8080"
+            default_timezone = "Europe/Paris"
+
+            [database]
+            url = "file.db"
+
+            [perplexity]
+            api_key = "from-file"
+
+            [reminders]
+            delivery = "log"
+            "#,
+        )
+        .unwrap()
+        .with_overrides(&env(&[
+            ("DATABASE_URL", "env.db"),
+            ("ANSWER_FALLBACKS", "perplexity"),
+            ("LOG_FORMAT", "json"),
+            ("WEBHOOK_TOLERANCE_SECS", "60"),
+        ]))
+        .unwrap();
+
+        assert_eq!(config.server.bind, "127.0.0.1:8080".parse().unwrap());
+        assert_eq!(config.server.default_timezone, Tz::Europe__Paris);
+        assert_eq!(config.database.url, "env.db");
+        assert!(config.database.migrate);
+        assert_eq!(config.perplexity.api_key.as_deref(), Some("from-file"));
+        assert_eq!(config.answers.fallbacks, Some(vec!["perplexity".to_string()]));
+        assert_eq!(config.reminders.delivery, DeliveryChannel::Log);
+        assert_eq!(config.log.format, LogFormat::Json);
+        assert_eq!(config.webhook.tolerance_secs, 60);
+        assert_eq!(config.answers.retries, 1);
+    }
+
+    #[test]
+    fn runs_on_defaults_with_just_a_database() {
+        let config = Config::default().with_overrides(&env(&[("DATABASE_URL", "db.sqlite")])).unwrap();
+        assert_eq!(config.server.bind.port(), 3000);
+        assert_eq!(config.reminders.delivery, DeliveryChannel::Sms);
+        assert!(config.answer_providers().is_empty());
+    }
+
+    #[test]
+    fn names_every_problem_at_once() {
+        let found = problems(Config::default().with_overrides(&env(&[
+            ("DEFAULT_TIMEZONE", "Mars/Olympus"),
+            ("ANSWER_RETRIES", "a few"),
+            ("REMINDER_DELIVERY", "pigeon"),
+            ("DISABLED_TOOLS", "LaunchRockets"),
+            ("ANSWER_PROVIDER", "openai"),
+            ("TWILIO_ACCOUNT_SID", "AC123"),
+        ])));
+
+        assert_eq!(found.len(), 6, "{:#?}", found);
+        assert!(found[0].starts_with("DEFAULT_TIMEZONE: "));
+        assert_eq!(found[1], "REMINDER_DELIVERY: \"pigeon\" is not a delivery channel, use sms, call or log");
+        assert!(found[2].starts_with("ANSWER_RETRIES: "));
+        assert_eq!(found[3], "there is no tool called \"LaunchRockets\" in tools");
+        assert_eq!(found[4], "answer provider \"openai\" is named but openai.base_url is not set");
+        assert_eq!(found[5], "twilio needs all of account_sid, auth_token and from_number");
+    }
+
+    #[test]
+    fn needs_a_database_only_when_asked() {
+        let config = Config::default().with_overrides(&env(&[("DATABASE_POOL_SIZE", "0")])).unwrap();
+        assert_eq!(
+            config.check_database().unwrap_err().0,
+            ["database.url (DATABASE_URL) is required", "database.pool_size has to be at least 1"]
+        );
+
+        let config = Config::default().with_overrides(&env(&[("DATABASE_URL", "db.sqlite")])).unwrap();
+        assert_eq!(config.check_database(), Ok(()));
+    }
+
+    #[test]
+    fn calls_need_the_voice_platform() {
+        let found = problems(
+            Config::default().with_overrides(&env(&[("DATABASE_URL", "db.sqlite"), ("REMINDER_DELIVERY", "call")])),
+        );
+        assert_eq!(found, ["reminders.delivery = \"call\" needs the [vapi] settings"]);
+    }
+
+    #[test]
+    fn answers_fit_in_the_tool_deadline() {
+        let slow = [("DATABASE_URL", "db.sqlite"), ("ANSWER_TIMEOUT_MS", "8000")];
+        assert_eq!(
+            problems(Config::default().with_overrides(&env(&slow))),
+            ["answers.timeout_ms, retries and backoff_ms add up to 16250 ms, more than the 14500 ms server.tool_deadline_ms leaves for answering"]
+        );
+        let patient = [slow[0], slow[1], ("TOOL_CALL_DEADLINE_MS", "20000")];
+        assert!(Config::default().with_overrides(&env(&patient)).is_ok());
+    }
+
+    #[test]
+    fn bounds_answer_retries() {
+        let found = problems(Config::default().with_overrides(&env(&[
+            ("DATABASE_URL", "db.sqlite"),
+            ("ANSWER_TIMEOUT_MS", "0"),
+            ("ANSWER_RETRIES", "4294967295"),
+            ("ANSWER_BACKOFF_MS", "86400000"),
+        ])));
+        assert_eq!(
+            found,
+            [
+                "answers.timeout_ms has to be more than 0",
+                "answers.retries can be at most 5",
+                "answers.backoff_ms can be at most 5000",
+                "answers.timeout_ms, retries and backoff_ms add up to 25000 ms, more than the 14500 ms server.tool_deadline_ms leaves for answering",
+            ]
+        );
+    }
+
+    #[test]
+    fn the_example_is_valid() {
+        let example = Config::from_toml(include_str!("../config.example.toml")).unwrap();
+        let config = example.with_overrides(&env(&[])).unwrap();
+        assert_eq!(config.database.url, "dumbassistant.db");
+        assert_eq!(config.log.max_body, None);
+    }
+
+    #[test]
+    fn rejects_misspelled_settings() {
+        let found = problems(Config::from_toml("[database]\nurll = \"db.sqlite\"\n"));
+        assert!(found[0].contains("unknown field `urll`"), "{:?}", found);
+    }
+}
@@ -0,0 +1,87 @@
+use std::time::Duration;
+use diesel::connection::SimpleConnection;
+use diesel::r2d2::{self, ConnectionManager, CustomizeConnection};
+use diesel::sqlite::{Sqlite, SqliteConnection};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use crate::error::Error;
+
+
+pub type Pool = r2d2::Pool<ConnectionManager<SqliteConnection>>;
+
+/// The `migrations/` directory, built into the binary.
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+/// How long a write waits for another connection's transaction before giving up
+/// with "database is locked".
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sets every new connection up for concurrent use: WAL lets readers carry on while
+/// someone writes, and the busy timeout makes writers queue instead of failing.
+#[derive(Debug)]
+struct Pragmas;
+
+impl CustomizeConnection<SqliteConnection, r2d2::Error> for Pragmas {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), r2d2::Error> {
+        conn.batch_execute(&format!(
+            "PRAGMA busy_timeout = {}; PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;",
+            BUSY_TIMEOUT.as_millis()
+        ))
+        .map_err(r2d2::Error::QueryError)
+    }
+}
+
+/// Pool of up to `max_size` connections to the SQLite database at `database_url`.
+pub fn pool(database_url: &str, max_size: u32) -> Result<Pool, r2d2::PoolError> {
+    r2d2::Pool::builder()
+        .max_size(max_size)
+        .connection_customizer(Box::new(Pragmas))
+        .build(ConnectionManager::new(database_url))
+}
+
+/// Runs `query` on a pooled connection in a blocking task, so waiting for the
+/// connection or the disk never stalls the async runtime.
+pub async fn run<T, F>(pool: &Pool, query: F) -> Result<T, Error>
+where
+    F: FnOnce(&mut SqliteConnection) -> Result<T, diesel::result::Error> + Send + 'static,
+    T: Send + 'static,
+{
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        Ok(query(&mut conn)?)
+    })
+    .await
+    .map_err(|e| Error::Crashed(e.to_string()))?
+}
+
+/// Names of the migrations the database hasn't had yet, oldest first.
+pub fn pending_migrations(conn: &mut SqliteConnection) -> Result<Vec<String>, Error> {
+    let pending = MigrationHarness::<Sqlite>::pending_migrations(conn, MIGRATIONS)
+        .map_err(|e| Error::Migration(e.to_string()))?;
+    Ok(pending.iter().map(|migration| migration.name().to_string()).collect())
+}
+
+/// Brings the schema up to date and returns the versions that were applied.
+pub fn run_pending_migrations(conn: &mut SqliteConnection) -> Result<Vec<String>, Error> {
+    let applied = conn
+        .run_pending_migrations(MIGRATIONS)
+        .map_err(|e| Error::Migration(e.to_string()))?;
+    Ok(applied.iter().map(ToString::to_string).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::Connection;
+
+    #[test]
+    fn migrates_a_fresh_database_once() {
+        let mut conn = SqliteConnection::establish(":memory:").unwrap();
+        let pending = pending_migrations(&mut conn).unwrap();
+        assert_eq!(pending.first().map(String::as_str), Some("2024-12-16-162449_create_reminders_table"));
+
+        assert_eq!(run_pending_migrations(&mut conn).unwrap().len(), pending.len());
+        assert!(pending_migrations(&mut conn).unwrap().is_empty());
+        assert!(run_pending_migrations(&mut conn).unwrap().is_empty());
+    }
+}
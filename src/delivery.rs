@@ -0,0 +1,39 @@
+use async_trait::async_trait;
+use std::fmt;
+use crate::models::{Reminder, User};
+
+
+/// A channel a due reminder can be handed to, e.g. SMS or a phone call.
+#[async_trait]
+pub trait Delivery: Send + Sync {
+    async fn deliver(&self, user: &User, reminder: &Reminder) -> Result<Receipt, DeliveryError>;
+}
+
+/// What a channel reports back after accepting a reminder, stored on the reminder row.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Receipt {
+    pub call_id: Option<String>,
+    pub call_status: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct DeliveryError(pub String);
+
+impl fmt::Display for DeliveryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "delivery failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for DeliveryError {}
+
+/// Only writes the reminder to the log. Used when no real channel is configured.
+pub struct LogDelivery;
+
+#[async_trait]
+impl Delivery for LogDelivery {
+    async fn deliver(&self, user: &User, reminder: &Reminder) -> Result<Receipt, DeliveryError> {
+        tracing::info!("Reminder due for {} ({}): {}", user.phone_number, reminder.id, reminder.message);
+        Ok(Receipt::default())
+    }
+}
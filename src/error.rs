@@ -0,0 +1,80 @@
+use std::fmt;
+use std::time::Duration;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+
+/// What can go wrong while handling a webhook, kept typed until it is reported.
+#[derive(Debug)]
+pub enum Error {
+    Database(diesel::result::Error),
+    /// No connection came free in the pool in time.
+    Pool(diesel::r2d2::PoolError),
+    /// The schema couldn't be brought up to date.
+    Migration(String),
+    /// A tool call's arguments didn't match the tool's schema, explained so the
+    /// assistant can fix them.
+    InvalidArguments(String),
+    /// A tool call was still running when the batch's deadline passed.
+    TimedOut(Duration),
+    /// A change was still running when the batch's deadline passed. It is left to
+    /// finish, so it may yet go through.
+    Unfinished(Duration),
+    /// A tool call panicked.
+    Crashed(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Database(e) => write!(f, "database error: {}", e),
+            Error::Pool(e) => write!(f, "no database connection: {}", e),
+            Error::Migration(reason) => write!(f, "migration failed: {}", reason),
+            Error::InvalidArguments(reason) => write!(f, "invalid arguments: {}", reason),
+            Error::TimedOut(after) => write!(f, "timed out after {:?}", after),
+            Error::Unfinished(after) => write!(
+                f,
+                "still running after {:?}, so it may or may not have gone through; check before trying it again",
+                after
+            ),
+            Error::Crashed(reason) => write!(f, "crashed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Database(e) => Some(e),
+            Error::Pool(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<diesel::result::Error> for Error {
+    fn from(e: diesel::result::Error) -> Self {
+        Error::Database(e)
+    }
+}
+
+impl From<diesel::r2d2::PoolError> for Error {
+    fn from(e: diesel::r2d2::PoolError) -> Self {
+        Error::Pool(e)
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Error::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::Pool(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Error::Migration(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::InvalidArguments(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::TimedOut(_) | Error::Unfinished(_) => StatusCode::GATEWAY_TIMEOUT,
+            Error::Crashed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        tracing::error!("{}", self);
+        (status, self.to_string()).into_response()
+    }
+}
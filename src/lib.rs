@@ -1,14 +1,62 @@
+#[cfg(test)]
 use diesel::prelude::*;
-use dotenvy::dotenv;
+#[cfg(test)]
 use diesel::sqlite::SqliteConnection;
-use std::env;
 
+pub mod answer_cache;
+pub mod answers;
+pub mod calls;
+pub mod config;
+pub mod db;
+pub mod delivery;
+pub mod error;
+pub mod models;
+pub mod perplexity;
+pub mod recurrence;
+pub mod reminders;
+pub mod request_log;
+pub mod scheduler;
+pub mod server;
+pub mod sms;
+pub mod time_expr;
+pub mod times;
+pub mod tools;
+pub mod users;
+pub mod webhook_auth;
+pub mod schema;
 
-pub fn establish_connection() -> SqliteConnection {
-    dotenv().ok();
 
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    SqliteConnection::establish(&database_url)
-        .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
+/// In-memory database with every migration applied, for unit tests.
+#[cfg(test)]
+pub(crate) fn test_connection() -> SqliteConnection {
+    let mut conn = SqliteConnection::establish(":memory:").unwrap();
+    db::run_pending_migrations(&mut conn).unwrap();
+    conn
 }
 
+/// Pool over a single in-memory database with every migration applied, for unit
+/// tests. It has one connection, since every connection to `:memory:` is a database
+/// of its own.
+#[cfg(test)]
+pub(crate) fn test_pool() -> db::Pool {
+    use diesel::r2d2::{ConnectionManager, CustomizeConnection, Error, Pool};
+
+    #[derive(Debug)]
+    struct Migrated;
+
+    impl CustomizeConnection<SqliteConnection, Error> for Migrated {
+        fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), Error> {
+            *conn = test_connection();
+            Ok(())
+        }
+    }
+
+    Pool::builder()
+        .max_size(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connection_timeout(std::time::Duration::from_secs(5))
+        .connection_customizer(Box::new(Migrated))
+        .build(ConnectionManager::new(":memory:"))
+        .unwrap()
+}
@@ -1,256 +1,401 @@
-use axum::{
-    extract::{State, Request},
-    routing::{post, get},
-    response::{Json, Response},
-    http::StatusCode,
-    body::Body,
-    middleware::{self, Next},
-    Router,
-};
-use serde_json::json;
-use axum::debug_handler;
-use tracing_subscriber::field::debug;
-use crate::lib::establish_connection;
-use crate::models::{Reminder, CreateReminder, ResponseWrapper, ToolCallResult, ToolCallResponse};
-use std::sync::{Arc, Mutex};
-use diesel::prelude::*;
-use serde::{Deserialize, Serialize};
-use tracing::Level;
-use tower_http::trace::{self, TraceLayer};
-
+use axum::body::Body;
+use axum::http::Request;
+use clap::{Args, Parser, Subcommand};
+use dumbassistant::config::{Config, LogFormat};
+use dumbassistant::db::{self, Pool};
+use dumbassistant::error::Error;
+use dumbassistant::models::{ReminderListing, User};
+use dumbassistant::recurrence::Recurrence;
+use dumbassistant::reminders;
+use dumbassistant::scheduler::Scheduler;
+use dumbassistant::server::{self, AppState};
+use dumbassistant::times;
+use dumbassistant::tools::ToolRegistry;
+use dumbassistant::users;
+use dumbassistant::webhook_auth::WebhookAuth;
+use std::path::PathBuf;
+use std::sync::Arc;
+use chrono::Utc;
+use chrono_tz::Tz;
+use diesel::{Connection, SqliteConnection};
 use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+use tracing_subscriber::EnvFilter;
+
+
+/// Reminders and answers for people calling in from a dumbphone.
+#[derive(Parser)]
+#[command(version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    /// TOML configuration file, `config.toml` when there is one
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    serve: ServeArgs,
+}
 
+#[derive(Subcommand)]
+enum Command {
+    /// Run the webhook server (what runs without a command)
+    Serve(ServeArgs),
+    /// List the migrations the database is missing and apply them
+    Migrate {
+        /// Only list them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Look at or change a caller's reminders
+    #[command(subcommand)]
+    Reminders(RemindersCommand),
+    /// Look at or register callers
+    #[command(subcommand)]
+    Users(UsersCommand),
+    /// The tools the voice assistant can call
+    #[command(subcommand)]
+    Tools(ToolsCommand),
+    /// Send a tool call through the webhook handler, in-process, and print what
+    /// the voice platform would get back
+    Simulate {
+        /// Name of the tool, e.g. GetUserReminders
+        tool: String,
+        /// Arguments as JSON
+        #[arg(long, default_value = "{}")]
+        args: String,
+        /// Phone number of the caller; without one it's a web call
+        #[arg(long)]
+        user: Option<String>,
+    },
+}
 
-pub mod models;
-pub mod lib;
-pub mod schema;
+#[derive(Args)]
+struct ServeArgs {
+    /// Leave the database as it is, for deploys that migrate separately
+    #[arg(long)]
+    no_migrate: bool,
+}
 
-async fn log_request(
-    req: Request,
-    next: Next,
-) -> Response {
-    let (parts, body) = req.into_parts();
-    
-    // Print headers
-    tracing::info!("Request Headers: {:#?}", parts.headers);
-    
-    // Get and print body
-    let bytes = body.collect().await
-        .map(|collected| collected.to_bytes())
-        .unwrap_or_default();
-    
-    tracing::info!("Request Body: {}", String::from_utf8_lossy(&bytes));
-    
-    // Reconstruct the request and continue
-    let req = Request::from_parts(parts, Body::from(bytes));
-    next.run(req).await
+#[derive(Subcommand)]
+enum RemindersCommand {
+    /// Everything the caller has set, in their local time
+    List {
+        #[arg(long)]
+        user: String,
+    },
+    /// Set a reminder, registering the caller if they are new
+    Add {
+        #[arg(long)]
+        user: String,
+        /// When, as the assistant would take it: "tomorrow at 9", "2025-03-01 14:00", …
+        #[arg(long)]
+        at: String,
+        /// Repeat rule, e.g. "FREQ=WEEKLY;BYDAY=MO"
+        #[arg(long)]
+        repeat: Option<Recurrence>,
+        message: String,
+    },
+    /// Delete one of the caller's reminders
+    Delete {
+        #[arg(long)]
+        user: String,
+        id: String,
+    },
 }
 
+#[derive(Subcommand)]
+enum UsersCommand {
+    /// Everyone who has called, oldest first
+    List,
+    /// Register a caller before their first call
+    Create {
+        phone_number: String,
+        /// IANA name of the zone they speak times in
+        #[arg(long)]
+        timezone: Option<Tz>,
+    },
+    /// Send the caller's questions to one of the configured answer providers, or
+    /// back to the default when no provider is given
+    SetProvider {
+        phone_number: String,
+        provider: Option<String>,
+    },
+}
 
+#[derive(Subcommand)]
+enum ToolsCommand {
+    /// Print the tool definitions to paste into the voice assistant
+    Schema,
+}
 
 #[tokio::main]
 async fn main() {
-    // Initialize tracing
-    if std::env::var_os("RUST_LOG").is_none() {
-        std::env::set_var("RUST_LOG", "debug,tower_http=debug");
+    let cli = Cli::parse();
+    let config = Config::load(cli.config.as_deref()).unwrap_or_else(|e| exit_with(e));
+    let command = cli.command.unwrap_or(Command::Serve(cli.serve));
+    if !matches!(command, Command::Tools(_)) {
+        config.check_database().unwrap_or_else(|e| exit_with(e));
     }
-    tracing_subscriber::fmt()
-        .with_target(false)
-        .compact()
-        .init();
-    // build our application with a single route
-    let app_state= Arc::new(Mutex::new(establish_connection()));
-    let app = Router::new()
-        .route("/tool-call", post(handle_tool_call))
-        .layer(
-            TraceLayer::new_for_http()
-                .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
-                .on_response(trace::DefaultOnResponse::new().level(Level::INFO))
-        )
-        .layer(middleware::from_fn(log_request))
-        .with_state(app_state);
 
-    // run our app with hyper, listening globally on port 3000
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    tracing::info!("listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
+    let result = match command {
+        Command::Serve(args) => {
+            serve(config, args).await;
+            Ok(())
+        }
+        Command::Migrate { dry_run } => migrate(&config, dry_run),
+        Command::Tools(ToolsCommand::Schema) => {
+            let definitions = ToolRegistry::from_config(&config).definitions(config.server.public_url.as_deref());
+            println!("{}", serde_json::to_string_pretty(&definitions).unwrap());
+            Ok(())
+        }
+        Command::Reminders(command) => {
+            init_tracing(&config, true);
+            manage_reminders(&config, command).await
+        }
+        Command::Users(command) => {
+            init_tracing(&config, true);
+            manage_users(&config, command).await
+        }
+        Command::Simulate { tool, args, user } => {
+            init_tracing(&config, true);
+            simulate(config, &tool, &args, user).await
+        }
+    };
+    if let Err(e) = result {
+        exit_with(e);
+    }
 }
 
-
-#[derive(Deserialize, Debug)]
-struct ToolCallRequest {
-    message: ToolCallMessage,
+/// Reports a failure and exits, before or instead of serving.
+fn exit_with(error: impl std::fmt::Display) -> ! {
+    eprintln!("{}", error);
+    std::process::exit(1);
 }
 
-#[derive(Deserialize, Debug)]
-struct ToolCallMessage {
-    #[serde(rename = "toolCalls")]
-    tool_calls: Vec<ToolCall>,
+/// Logs in the configured format, keeping what `log.filter` (or `RUST_LOG`) selects.
+/// Commands other than the server log to stderr, keeping stdout for their output.
+fn init_tracing(config: &Config, to_stderr: bool) {
+    let filter = EnvFilter::new(&config.log.filter);
+    let writer = move || -> Box<dyn std::io::Write> {
+        if to_stderr {
+            Box::new(std::io::stderr())
+        } else {
+            Box::new(std::io::stdout())
+        }
+    };
+    match config.log.format {
+        LogFormat::Json => tracing_subscriber::fmt()
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_env_filter(filter)
+            .with_writer(writer)
+            .init(),
+        LogFormat::Text => tracing_subscriber::fmt()
+            .with_target(false)
+            .compact()
+            .with_env_filter(filter)
+            .with_writer(writer)
+            .init(),
+    }
 }
 
-#[derive(Deserialize, Debug)]
-struct ToolCall {
-    id: String,
-    function: FunctionCall,
+fn open_pool(config: &Config) -> Pool {
+    db::pool(&config.database.url, config.database.pool_size).unwrap_or_else(|e| exit_with(e))
 }
 
-#[derive(Deserialize, Debug)]
-struct FunctionCall {
-    name: String,
-    arguments: FunctionArgs,
-}
+async fn serve(config: Config, args: ServeArgs) {
+    init_tracing(&config, false);
+    let pool = open_pool(&config);
+    if !config.database.migrate || args.no_migrate {
+        tracing::info!("Not migrating the database, as asked");
+    } else {
+        let applied = pool
+            .get()
+            .map_err(Error::from)
+            .and_then(|mut conn| db::run_pending_migrations(&mut conn))
+            .unwrap_or_else(|e| exit_with(e));
+        for version in applied {
+            tracing::info!("Applied migration {}", version);
+        }
+    }
 
-#[derive(Deserialize, Debug, Clone)]
-#[serde(untagged)] // This allows for multiple possible structures
-enum FunctionArgs {
-    Create(CreateReminderArgs),
-    Message(PerplexityMessageArgs),
-    Empty(EmptyArgs),
-}
+    let bind = config.server.bind;
+    let state = AppState::from_config(Arc::new(config), pool);
+    match state.answers.default_provider() {
+        Some(provider) => tracing::info!("Answering questions with {}", provider.name()),
+        None => tracing::warn!("No answer provider is configured, questions can't be answered"),
+    }
 
-#[derive(Deserialize, Debug, Clone)]
-struct EmptyArgs {}
+    // fire reminders in the background as their remind_at passes
+    let scheduler = Scheduler::new(state.pool.clone(), state.reminder_delivery())
+        .with_default_timezone(state.config.server.default_timezone);
+    tokio::spawn(scheduler.run());
 
-#[derive(Deserialize, Debug, Clone)]
-struct PerplexityMessageArgs {
-    message: String,
-}
+    if !WebhookAuth::from_config(&state.config).is_enabled() {
+        tracing::warn!("webhook.secret is not set, anyone can call the webhooks");
+    }
 
-#[derive(Deserialize, Debug, Clone)]
-struct CreateReminderArgs {
-    message: String,
-    remind_at: String,
+    let listener = tokio::net::TcpListener::bind(bind)
+        .await
+        .unwrap_or_else(|e| exit_with(format!("can't listen on {}: {}", bind, e)));
+    tracing::info!("listening on {}", listener.local_addr().unwrap());
+    axum::serve(listener, server::router(state)).await.unwrap();
 }
 
-
-#[debug_handler]
-async fn handle_tool_call(
-    State(pool): State<Arc<Mutex<SqliteConnection>>>,
-    Json(payload): Json<ToolCallRequest>,
-) -> Result<Json<ResponseWrapper>, (StatusCode, String)> {
-    tracing::info!("Handling tool call");
-
-    let mut results = Vec::new();
-
-    for tool_call in payload.message.tool_calls.iter() {
-        tracing::info!("Handling tool call : {:#?}", tool_call);
-        let result = match (&tool_call.function.name, &tool_call.function.arguments) {
-            (name, FunctionArgs::Empty(_)) if name == "GetUserReminders" => {
-                let mut conn = pool.lock().unwrap();
-                tracing::info!("Listing all reminders");
-                let reminders = list_reminders(&mut conn)
-                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-                ToolCallResponse::Multiple(reminders)
-            },
-            (name, FunctionArgs::Create(args)) if name == "StoreUserReminder" => {
-                let mut conn = pool.lock().unwrap();
-                tracing::info!("Creating reminder with args: {:#?}", args);
-                let reminder = create_reminder(&mut conn, args)
-                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-                ToolCallResponse::Single(reminder)
-            },
-            (name, FunctionArgs::Empty(_)) if name == "DeleteAllReminders" => {
-                let mut conn = pool.lock().unwrap();
-                tracing::info!("Deleting all reminders");
-                let deleted_count = delete_all_reminders(&mut conn)
-                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-                ToolCallResponse::Multiple(Vec::new()) // Return empty vector after deletion
-            },
-            (name, FunctionArgs::Message(args)) if name == "AskPerplexity" => {
-                tracing::info!("Asking Perplexity");
-                let response = ask_perplexity(&args.message)
-                    .await
-                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-                ToolCallResponse::Message(response)
-            },
-            _ => {
-                tracing::warn!("Unknown function call: {:#?}", tool_call.function.name);
-                ToolCallResponse::Message("Unknown function call".to_string())
-            }
-
-        };
-
-        results.push(ToolCallResult {
-            toolCallId: tool_call.id.clone(),
-            result,
-        });
+/// Lists the migrations the database is missing and applies them.
+fn migrate(config: &Config, dry_run: bool) -> Result<(), Error> {
+    let mut conn = SqliteConnection::establish(&config.database.url).map_err(|e| Error::Migration(e.to_string()))?;
+    let pending = db::pending_migrations(&mut conn)?;
+    if pending.is_empty() {
+        println!("The database is up to date.");
+        return Ok(());
     }
-
-    tracing::info!("Returning results: {:#?}", results);
-    Ok(Json(ResponseWrapper { results }))
+    for name in &pending {
+        println!("Pending: {}", name);
+    }
+    if !dry_run {
+        let applied = db::run_pending_migrations(&mut conn)?;
+        println!("Applied {} migrations.", applied.len());
+    }
+    Ok(())
 }
 
+/// The registered caller with this number.
+async fn existing_user(pool: &Pool, phone_number: &str) -> Result<User, Error> {
+    let number = phone_number.to_string();
+    db::run(pool, move |conn| users::find_by_phone(conn, &number))
+        .await?
+        .ok_or_else(|| Error::InvalidArguments(format!("nobody has called from {}", phone_number)))
+}
 
-async fn ask_perplexity(message: &str) -> Result<String, reqwest::Error> {
-    let api_key = std::env::var("PERPLEXITY_API_KEY").expect("PERPLEXITY_API_KEY must be set");
-    let client = reqwest::Client::new();
-    
-    let payload = json!({
-        "model": "llama-3.1-sonar-small-128k-online",
-        "messages": [
-            {
-                "role": "system",
-                "content": "Be precise and concise."
-            },
-            {
-                "role": "user",
-                "content": message
+async fn manage_reminders(config: &Config, command: RemindersCommand) -> Result<(), Error> {
+    let pool = open_pool(config);
+    let default_timezone = config.server.default_timezone;
+    match command {
+        RemindersCommand::List { user } => {
+            let user = existing_user(&pool, &user).await?;
+            let tz = user.timezone(default_timezone);
+            let listed = db::run(&pool, move |conn| reminders::list_reminders(conn, &user)).await?;
+            if listed.is_empty() {
+                println!("No reminders.");
             }
-        ]
-    });
-
-    let response = client
-        .post("https://api.perplexity.ai/chat/completions")
-        .header("accept", "application/json")
-        .header("content-type", "application/json")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .json(&payload)
-        .send()
-        .await?;
-
-    let result = response.text().await?;
-    println!("{}", result);
-    Ok(result)
+            for reminder in listed {
+                let listing = ReminderListing::new(reminder, tz);
+                let repeat = listing.reminder.recurrence.as_deref().map(|rule| format!("  ({})", rule)).unwrap_or_default();
+                println!("{}  {}  {}{}", listing.reminder.id, listing.local_time, listing.reminder.message, repeat);
+            }
+        }
+        RemindersCommand::Add { user, at, repeat, message } => {
+            if let Some(rule) = &repeat {
+                rule.validate().map_err(Error::InvalidArguments)?;
+            }
+            let user = db::run(&pool, move |conn| users::find_or_create_by_phone(conn, &user)).await?;
+            let tz = user.timezone(default_timezone);
+            let due_at = times::parse_remind_at(&at, tz, Utc::now()).map_err(|e| Error::InvalidArguments(e.to_string()))?;
+            let reminder = db::run(&pool, move |conn| reminders::create_reminder(conn, &user, &message, due_at, repeat.as_ref(), tz)).await?;
+            println!("{}  {}", reminder.id, ReminderListing::new(reminder.clone(), tz).local_time);
+        }
+        RemindersCommand::Delete { user, id } => {
+            let user = existing_user(&pool, &user).await?;
+            let deleted = db::run(&pool, move |conn| reminders::delete_reminder(conn, &user, &id)).await?;
+            match deleted {
+                Some(reminder) => println!("Deleted {}", reminder.id),
+                None => return Err(Error::InvalidArguments("the caller has no such reminder".to_string())),
+            }
+        }
+    }
+    Ok(())
 }
 
-fn create_reminder(
-    conn: &mut SqliteConnection,
-    args: &CreateReminderArgs,
-) -> Result<Reminder, diesel::result::Error> {
-    tracing::info!("Creating a new reminder");
-    tracing::info!("Message: {}", args.message);
-    tracing::info!("Remind at: {}", args.remind_at);
-    use crate::schema::reminders::dsl::*;
-
-
-    let new_reminder = Reminder::new(args.message.clone(), args.remind_at.clone());
-    
-    let result = diesel::insert_into(reminders)
-        .values(&new_reminder)
-        .execute(conn);
-
-    tracing::info!("result from the insert: {:#?}", result);
-
-    tracing::info!("Created reminder: {:#?}", new_reminder);
-    Ok(new_reminder)
+async fn manage_users(config: &Config, command: UsersCommand) -> Result<(), Error> {
+    let pool = open_pool(config);
+    match command {
+        UsersCommand::List => {
+            for user in db::run(&pool, users::list_users).await? {
+                println!(
+                    "{}  {}  {}  {}  {}",
+                    user.id,
+                    user.phone_number,
+                    user.timezone.as_deref().unwrap_or("-"),
+                    user.answer_provider.as_deref().unwrap_or("-"),
+                    user.created_at.and_utc().to_rfc3339()
+                );
+            }
+        }
+        UsersCommand::Create { phone_number, timezone } => {
+            let user = db::run(&pool, move |conn| {
+                let user = users::find_or_create_by_phone(conn, &phone_number)?;
+                match timezone {
+                    Some(tz) => users::set_timezone(conn, &user, tz),
+                    None => Ok(user),
+                }
+            })
+            .await?;
+            println!("{}  {}", user.id, user.phone_number);
+        }
+        UsersCommand::SetProvider { phone_number, provider } => {
+            let configured = config.answer_providers();
+            if let Some(name) = provider.as_deref().filter(|name| !configured.contains(name)) {
+                return Err(Error::InvalidArguments(format!(
+                    "{} is not a configured answer provider, expected one of: {}",
+                    name,
+                    configured.join(", ")
+                )));
+            }
+            let user = existing_user(&pool, &phone_number).await?;
+            let user = db::run(&pool, move |conn| users::set_answer_provider(conn, &user, provider.as_deref())).await?;
+            println!("{}  {}", user.phone_number, user.answer_provider.as_deref().unwrap_or("default"));
+        }
+    }
+    Ok(())
 }
 
+/// Posts a tool-call webhook, as the voice platform would send it, to the router
+/// the server runs, and prints the response.
+async fn simulate(config: Config, tool: &str, args: &str, user: Option<String>) -> Result<(), Error> {
+    let arguments: Value = serde_json::from_str(args)
+        .map_err(|e| Error::InvalidArguments(format!("--args is not JSON: {}", e)))?;
+    let now = Utc::now();
+    let payload = json!({
+        "message": {
+            "type": "tool-calls",
+            "timestamp": now.timestamp_millis(),
+            "toolCalls": [{
+                "id": "call_simulated",
+                "type": "function",
+                "function": { "name": tool, "arguments": arguments }
+            }],
+            "customer": user.map(|number| json!({ "number": number })),
+        }
+    });
+    let body = payload.to_string();
 
-
-fn list_reminders(
-    conn: &mut SqliteConnection
-) -> Result<Vec<Reminder>, diesel::result::Error> {
-    tracing::debug!("Listing all reminders");
-    use crate::schema::reminders::dsl::*;
-
-    reminders.load::<Reminder>(&mut *conn)
-}
-
-fn delete_all_reminders(
-    conn: &mut SqliteConnection
-) -> Result<usize, diesel::result::Error> {
-    use crate::schema::reminders::dsl::*;
-    
-    diesel::delete(reminders).execute(conn)
+    let pool = open_pool(&config);
+    let state = AppState::from_config(Arc::new(config), pool);
+    let mut request = Request::post("/tool-call").header("content-type", "application/json");
+    for (name, value) in WebhookAuth::from_config(&state.config).credentials(body.as_bytes(), now.timestamp()) {
+        request = request.header(name, value);
+    }
+    let response = server::router(state)
+        .oneshot(request.body(Body::from(body)).unwrap())
+        .await
+        .unwrap_or_else(|never| match never {});
+
+    let status = response.status();
+    let bytes = response
+        .into_body()
+        .collect()
+        .await
+        .map_err(|e| Error::Crashed(e.to_string()))?
+        .to_bytes();
+    match serde_json::from_slice::<Value>(&bytes) {
+        Ok(json) => println!("{}", serde_json::to_string_pretty(&json).unwrap()),
+        Err(_) => println!("{}", String::from_utf8_lossy(&bytes)),
+    }
+    if !status.is_success() {
+        return Err(Error::InvalidArguments(format!("the server answered {}", status)));
+    }
+    Ok(())
 }
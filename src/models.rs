@@ -1,8 +1,11 @@
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
-use super::schema::reminders;
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use super::schema::{answer_cache, reminders, users};
 use diesel::{Queryable, Insertable};
+use crate::error::Error;
+use crate::recurrence::{self, Recurrence};
 
 
 #[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
@@ -10,7 +13,50 @@ use diesel::{Queryable, Insertable};
 pub struct Reminder {
     pub id: String,
     pub message: String,
-    pub remind_at: String,
+    /// UTC
+    #[serde(with = "utc_timestamp")]
+    pub remind_at: NaiveDateTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delivered_at: Option<NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub call_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub call_status: Option<String>,
+    #[serde(skip)]
+    pub user_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<String>,
+    /// Failed deliveries of the pending occurrence
+    #[serde(skip)]
+    pub delivery_attempts: i32,
+    /// When a failed delivery is tried again, UTC
+    #[serde(skip)]
+    pub retry_at: Option<NaiveDateTime>,
+}
+
+#[derive(Queryable, Insertable, Serialize, Debug, Clone)]
+#[diesel(table_name = users)]
+pub struct User {
+    pub id: String,
+    pub phone_number: String,
+    pub created_at: NaiveDateTime,
+    /// IANA name, e.g. `Europe/Helsinki`
+    pub timezone: Option<String>,
+    /// Name of the provider answering this caller's questions
+    pub answer_provider: Option<String>,
+}
+
+/// A stored answer, keyed on the normalized question and the asker's locale.
+#[derive(Queryable, Insertable, Debug, Clone)]
+#[diesel(table_name = answer_cache)]
+pub struct CachedAnswer {
+    pub question: String,
+    pub locale: String,
+    pub answer: String,
+    /// JSON array of source URLs
+    pub citations: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
 }
 
 #[derive(Deserialize)]
@@ -20,17 +66,99 @@ pub struct CreateReminder {
 }
 
 impl Reminder {
-    pub fn new(message: String, remind_at: String) -> Self {
+    pub fn new(user_id: String, message: String, remind_at: DateTime<Utc>) -> Self {
         Reminder {
             id: Uuid::new_v4().to_string(),
             message,
-            remind_at,
+            remind_at: remind_at.naive_utc(),
+            delivered_at: None,
+            call_id: None,
+            call_status: None,
+            user_id: Some(user_id),
+            recurrence: None,
+            delivery_attempts: 0,
+            retry_at: None,
         }
     }
     pub fn into_datetime(&self) -> DateTime<Utc> {
-        DateTime::parse_from_rfc3339(&self.remind_at)
-            .unwrap()
-            .with_timezone(&Utc)
+        self.remind_at.and_utc()
+    }
+    /// The parsed repeat rule, ignoring one that can no longer be read.
+    pub fn recurrence(&self) -> Option<Recurrence> {
+        let rule = self.recurrence.as_deref()?;
+        rule.parse()
+            .inspect_err(|e| tracing::warn!("Ignoring recurrence of reminder {}: {}", self.id, e))
+            .ok()
+    }
+}
+
+/// A reminder as listed to the assistant, in the caller's local time, with the next
+/// few times a repeating one goes off.
+#[derive(Serialize, Debug)]
+pub struct ReminderListing {
+    #[serde(flatten)]
+    pub reminder: Reminder,
+    pub local_time: String,
+    pub timezone: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub upcoming: Vec<String>,
+}
+
+impl ReminderListing {
+    pub fn new(reminder: Reminder, tz: Tz) -> Self {
+        let local = |at: DateTime<Utc>| at.with_timezone(&tz).to_rfc3339_opts(SecondsFormat::Secs, true);
+        let upcoming = match reminder.recurrence() {
+            Some(rule) => rule
+                .occurrences(reminder.into_datetime(), recurrence::UPCOMING_LIMIT, tz)
+                .into_iter()
+                .map(local)
+                .collect(),
+            None => Vec::new(),
+        };
+        ReminderListing {
+            local_time: local(reminder.into_datetime()),
+            timezone: tz.name().to_string(),
+            reminder,
+            upcoming,
+        }
+    }
+}
+
+impl User {
+    pub fn new(phone_number: String) -> Self {
+        User {
+            id: Uuid::new_v4().to_string(),
+            phone_number,
+            created_at: Utc::now().naive_utc(),
+            timezone: None,
+            answer_provider: None,
+        }
+    }
+
+    /// The zone the caller speaks times in, `fallback` until they tell us theirs.
+    pub fn timezone(&self, fallback: Tz) -> Tz {
+        match self.timezone.as_deref().map(str::parse::<Tz>) {
+            Some(Ok(tz)) => tz,
+            Some(Err(e)) => {
+                tracing::warn!("Ignoring timezone of user {}: {}", self.id, e);
+                fallback
+            }
+            None => fallback,
+        }
+    }
+}
+
+/// Reminder times are stored as naive UTC but shown to the assistant with an explicit offset.
+mod utc_timestamp {
+    use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(at: &NaiveDateTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&at.and_utc().to_rfc3339_opts(SecondsFormat::Secs, true))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDateTime, D::Error> {
+        Ok(DateTime::<Utc>::deserialize(deserializer)?.naive_utc())
     }
 }
 
@@ -39,13 +167,33 @@ impl Reminder {
 pub enum ToolCallResponse {
     Single(Reminder),
     Multiple(Vec<Reminder>),
+    Listing(Vec<ReminderListing>),
     Message(String),
 }
 
+/// The outcome of one tool call in a batch: its `result`, or the `error` that
+/// stopped it, which the voice platform passes on to the assistant.
 #[derive(Serialize, Debug)]
 pub struct ToolCallResult {
-    pub toolCallId: String,
-    pub result: ToolCallResponse,
+    #[serde(rename = "toolCallId")]
+    pub tool_call_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<ToolCallResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ToolCallResult {
+    pub fn new(tool_call_id: String, outcome: Result<ToolCallResponse, Error>) -> Self {
+        match outcome {
+            Ok(result) => ToolCallResult { tool_call_id, result: Some(result), error: None },
+            Err(e) => ToolCallResult::failed(tool_call_id, &e),
+        }
+    }
+
+    pub fn failed(tool_call_id: String, error: &Error) -> Self {
+        ToolCallResult { tool_call_id, result: None, error: Some(error.to_string()) }
+    }
 }
 
 #[derive(Serialize, Debug)]
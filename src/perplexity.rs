@@ -0,0 +1,91 @@
+use async_trait::async_trait;
+use crate::answers::{Answer, AnswerError, AnswerProvider, ChatCompletions};
+use crate::config::Config;
+
+
+pub const PERPLEXITY_BASE_URL: &str = "https://api.perplexity.ai";
+pub const PERPLEXITY_MODEL: &str = "llama-3.1-sonar-small-128k-online";
+
+/// Perplexity's chat-completions API, which searches the web and cites its sources.
+pub struct Perplexity {
+    inner: ChatCompletions,
+}
+
+impl Perplexity {
+    pub fn new(base_url: &str, api_key: &str, model: &str) -> Self {
+        Perplexity {
+            inner: ChatCompletions::new("perplexity", base_url, model).with_api_key(api_key),
+        }
+    }
+
+    pub fn with_system_prompt(self, system_prompt: &str) -> Self {
+        Perplexity { inner: self.inner.with_system_prompt(system_prompt) }
+    }
+
+    /// Perplexity with the `[perplexity]` settings, when it has an `api_key`.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let perplexity = &config.perplexity;
+        let api_key = perplexity.api_key.as_deref()?;
+        Some(Perplexity::new(&perplexity.base_url, api_key, &perplexity.model)
+            .with_system_prompt(&config.answers.system_prompt))
+    }
+}
+
+#[async_trait]
+impl AnswerProvider for Perplexity {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn ask(&self, question: &str) -> Result<Answer, AnswerError> {
+        self.inner.ask(question).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn parses_completion_into_answer() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(header("authorization", "Bearer key"))
+            .and(body_partial_json(json!({
+                "model": PERPLEXITY_MODEL,
+                "messages": [{ "role": "system" }, { "role": "user", "content": "capital of Finland?" }]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "3c90c3cc",
+                "model": PERPLEXITY_MODEL,
+                "object": "chat.completion",
+                "created": 1736500000,
+                "citations": ["https://en.wikipedia.org/wiki/Helsinki"],
+                "choices": [{
+                    "index": 0,
+                    "finish_reason": "stop",
+                    "message": { "role": "assistant", "content": "The capital is **Helsinki**[1]." }
+                }],
+                "usage": { "prompt_tokens": 12, "completion_tokens": 8, "total_tokens": 20 }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let answer = Perplexity::new(&server.uri(), "key", PERPLEXITY_MODEL)
+            .ask("capital of Finland?")
+            .await
+            .unwrap();
+        assert_eq!(answer.text, "The capital is Helsinki.");
+        assert_eq!(answer.citations, vec!["https://en.wikipedia.org/wiki/Helsinki"]);
+        assert_eq!(answer.usage.map(|usage| usage.total_tokens), Some(20));
+        assert_eq!(
+            answer.with_sources(),
+            "The capital is Helsinki.\n\nSources:\n[1] https://en.wikipedia.org/wiki/Helsinki"
+        );
+    }
+}
@@ -0,0 +1,600 @@
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveTime, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use crate::times;
+
+
+/// How many upcoming occurrences `GetUserReminders` shows for a recurring reminder.
+pub const UPCOMING_LIMIT: usize = 5;
+
+/// Steps to look ahead when a monthly rule keeps landing on days that don't exist,
+/// e.g. the 31st every other month, or the 29th of February every year.
+const MAX_MONTHLY_STEPS: i64 = 48;
+
+/// Longest repeat intervals, a year in each unit; anything longer is more likely a
+/// misheard number than a reminder anyone wants.
+const MAX_DAILY_INTERVAL: u32 = 365;
+const MAX_WEEKLY_INTERVAL: u32 = 52;
+const MAX_MONTHLY_INTERVAL: u32 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+/// A subset of iCalendar RRULE: `FREQ`, `INTERVAL`, `BYDAY`, `BYHOUR`, `BYMINUTE`,
+/// `BYSECOND`, `UNTIL` and `COUNT`.
+///
+/// Stored on the reminder as its RRULE string. `count` is the number of occurrences
+/// left including the pending one, and is decremented every time the reminder fires.
+///
+/// Occurrences keep their wall-clock time in the user's zone, so a daily 8:00 reminder
+/// stays at 8:00 across daylight saving changes. The time is pinned in the rule when
+/// the reminder is created, so an occurrence pushed out of a skipped hour or snoozed
+/// doesn't drag the ones after it along. Rules stored before that pin it the first
+/// time they repeat.
+#[derive(Debug, Clone, PartialEq, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    /// Repeat every this many days, weeks or months
+    #[serde(default = "default_interval")]
+    #[schemars(range(min = 1))]
+    pub interval: u32,
+    /// Only on these days of the week
+    #[serde(default)]
+    pub by_weekday: Vec<Weekday>,
+    /// Last time it may go off
+    pub until: Option<DateTime<Utc>>,
+    /// How many times it goes off in total
+    #[schemars(range(min = 1))]
+    pub count: Option<u32>,
+    /// Local time every occurrence is at, taken from the first one
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub time_of_day: Option<NaiveTime>,
+}
+
+fn default_interval() -> u32 {
+    1
+}
+
+impl Recurrence {
+    /// Explains what is wrong in a way the assistant can repeat to the caller.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval == 0 {
+            return Err("The repeat interval has to be at least one.".to_string());
+        }
+        let (max, unit) = match self.frequency {
+            Frequency::Daily => (MAX_DAILY_INTERVAL, "days"),
+            Frequency::Weekly => (MAX_WEEKLY_INTERVAL, "weeks"),
+            Frequency::Monthly => (MAX_MONTHLY_INTERVAL, "months"),
+        };
+        if self.interval > max {
+            return Err(format!("A reminder can repeat at most every {} {}.", max, unit));
+        }
+        if self.count == Some(0) {
+            return Err("A repeating reminder has to happen at least once.".to_string());
+        }
+        if self.frequency == Frequency::Monthly && !self.by_weekday.is_empty() {
+            return Err("Monthly reminders can't be limited to weekdays yet.".to_string());
+        }
+        // every seventh day is always the same weekday, so most of the days given
+        // would never come up
+        if self.frequency == Frequency::Daily && self.interval.is_multiple_of(7) && !self.by_weekday.is_empty() {
+            return Err(format!(
+                "Every {} days always falls on the same weekday. Make it a weekly reminder on that day instead.",
+                self.interval
+            ));
+        }
+        Ok(())
+    }
+
+    /// Where a series asked for at `start` begins: `start` itself when it falls on one
+    /// of the rule's weekdays, otherwise the first occurrence after it. The rule comes
+    /// back with its time of day pinned to `start`'s, so later changes to this one
+    /// occurrence, like a snooze, don't move the rest of the series.
+    pub fn first_occurrence(&self, start: DateTime<Utc>, tz: Tz) -> (DateTime<Utc>, Recurrence) {
+        let local = start.with_timezone(&tz);
+        let rule = self.pinned_to(local.time());
+        if self.matches_weekday(local.date_naive()) {
+            return (start, rule);
+        }
+        let first = rule.next_after(start, local.time(), tz).unwrap_or(start);
+        (first, rule)
+    }
+
+    /// The rule keeping its time of day, or taking `time` when it has none yet.
+    pub fn pinned_to(&self, time: NaiveTime) -> Recurrence {
+        Recurrence { time_of_day: Some(self.time_of_day.unwrap_or(time)), ..self.clone() }
+    }
+
+    /// The occurrence after `current` together with the rule to store for it, or
+    /// `None` once `until` or `count` is exhausted.
+    pub fn advance(&self, current: DateTime<Utc>, tz: Tz) -> Option<(DateTime<Utc>, Recurrence)> {
+        let count = match self.count {
+            Some(left) if left <= 1 => return None,
+            Some(left) => Some(left - 1),
+            None => None,
+        };
+        let time = self.time_of_day.unwrap_or_else(|| current.with_timezone(&tz).time());
+        let next = self.next_after(current, time, tz)?;
+        if self.until.is_some_and(|until| next > until) {
+            return None;
+        }
+        Some((next, Recurrence { count, time_of_day: Some(time), ..self.clone() }))
+    }
+
+    /// `start` and the occurrences following it, at most `limit` in total.
+    pub fn occurrences(&self, start: DateTime<Utc>, limit: usize, tz: Tz) -> Vec<DateTime<Utc>> {
+        let mut occurrences = Vec::new();
+        let mut next = Some((start, self.clone()));
+        while let Some((at, rule)) = next {
+            if occurrences.len() >= limit {
+                break;
+            }
+            occurrences.push(at);
+            next = rule.advance(at, tz);
+        }
+        occurrences
+    }
+
+    fn next_after(&self, current: DateTime<Utc>, time: NaiveTime, tz: Tz) -> Option<DateTime<Utc>> {
+        let date = current.with_timezone(&tz).date_naive();
+        let next_date = match self.frequency {
+            Frequency::Daily => self.next_daily(date),
+            Frequency::Weekly => self.next_weekly(date),
+            Frequency::Monthly => self.next_monthly(date),
+        }?;
+        Some(times::localize(tz, next_date.and_time(time)))
+    }
+
+    fn matches_weekday(&self, date: NaiveDate) -> bool {
+        self.by_weekday.is_empty() || self.by_weekday.contains(&date.weekday())
+    }
+
+    fn next_daily(&self, date: NaiveDate) -> Option<NaiveDate> {
+        let step = Days::new(u64::from(self.interval));
+        let mut candidate = date.checked_add_days(step)?;
+        // every weekday of the week recurs within seven steps, or never will
+        for _ in 0..7 {
+            if self.matches_weekday(candidate) {
+                return Some(candidate);
+            }
+            candidate = candidate.checked_add_days(step)?;
+        }
+        None
+    }
+
+    fn next_weekly(&self, date: NaiveDate) -> Option<NaiveDate> {
+        let interval = Days::new(7 * u64::from(self.interval));
+        if self.by_weekday.is_empty() {
+            return date.checked_add_days(interval);
+        }
+
+        let mut days: Vec<u32> = self.by_weekday
+            .iter()
+            .map(|day| day.num_days_from_monday())
+            .collect();
+        days.sort_unstable();
+
+        let today = date.weekday().num_days_from_monday();
+        let week_start = date.checked_sub_days(Days::new(u64::from(today)))?;
+        match days.iter().find(|&&day| day > today) {
+            Some(&day) => week_start.checked_add_days(Days::new(u64::from(day))),
+            None => week_start
+                .checked_add_days(interval)?
+                .checked_add_days(Days::new(u64::from(days[0]))),
+        }
+    }
+
+    fn next_monthly(&self, date: NaiveDate) -> Option<NaiveDate> {
+        let month_index = i64::from(date.year()) * 12 + i64::from(date.month0());
+        (1..=MAX_MONTHLY_STEPS)
+            .map(|step| month_index + step * i64::from(self.interval))
+            .find_map(|index| {
+                let year = i32::try_from(index.div_euclid(12)).ok()?;
+                NaiveDate::from_ymd_opt(year, index.rem_euclid(12) as u32 + 1, date.day())
+            })
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+        };
+        write!(f, "FREQ={}", frequency)?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_weekday.is_empty() {
+            let days: Vec<&str> = self.by_weekday.iter().map(|day| weekday_code(*day)).collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if let Some(time) = self.time_of_day {
+            write!(f, ";BYHOUR={};BYMINUTE={}", time.hour(), time.minute())?;
+            if time.second() != 0 {
+                write!(f, ";BYSECOND={}", time.second())?;
+            }
+        }
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format("%Y%m%dT%H%M%SZ"))?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let mut frequency = None;
+        let mut recurrence = Recurrence {
+            frequency: Frequency::Daily,
+            interval: 1,
+            by_weekday: Vec::new(),
+            until: None,
+            count: None,
+            time_of_day: None,
+        };
+        let (mut hour, mut minute, mut second) = (None, None, None);
+
+        for part in rule.trim().trim_start_matches("RRULE:").split(';') {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("malformed rule part {:?}", part))?;
+            match key {
+                "FREQ" => {
+                    frequency = Some(match value {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        other => return Err(format!("unsupported frequency {:?}", other)),
+                    })
+                }
+                "INTERVAL" => {
+                    recurrence.interval = value.parse().map_err(|_| format!("bad interval {:?}", value))?
+                }
+                "BYDAY" => {
+                    recurrence.by_weekday = value
+                        .split(',')
+                        .map(|code| parse_weekday_code(code).ok_or_else(|| format!("bad weekday {:?}", code)))
+                        .collect::<Result<_, _>>()?
+                }
+                "UNTIL" => {
+                    let until = chrono::NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ")
+                        .map_err(|_| format!("bad until {:?}", value))?;
+                    recurrence.until = Some(until.and_utc());
+                }
+                "COUNT" => {
+                    recurrence.count = Some(value.parse().map_err(|_| format!("bad count {:?}", value))?)
+                }
+                "BYHOUR" => hour = Some(value.parse().map_err(|_| format!("bad hour {:?}", value))?),
+                "BYMINUTE" => minute = Some(value.parse().map_err(|_| format!("bad minute {:?}", value))?),
+                "BYSECOND" => second = Some(value.parse().map_err(|_| format!("bad second {:?}", value))?),
+                other => return Err(format!("unsupported rule part {:?}", other)),
+            }
+        }
+
+        recurrence.frequency = frequency.ok_or("rule has no FREQ")?;
+        if hour.is_some() || minute.is_some() || second.is_some() {
+            let time = NaiveTime::from_hms_opt(hour.unwrap_or(0), minute.unwrap_or(0), second.unwrap_or(0))
+                .ok_or_else(|| format!("bad time of day in {:?}", rule))?;
+            recurrence.time_of_day = Some(time);
+        }
+        Ok(recurrence)
+    }
+}
+
+fn weekday_code(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn parse_weekday_code(code: &str) -> Option<Weekday> {
+    [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ]
+    .into_iter()
+    .find(|day| weekday_code(*day) == code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use chrono_tz::{Europe::Helsinki, UTC};
+
+    fn at(y: i32, m: u32, d: u32, h: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, 0, 0).unwrap()
+    }
+
+    fn rule(frequency: Frequency) -> Recurrence {
+        Recurrence {
+            frequency,
+            interval: 1,
+            by_weekday: Vec::new(),
+            until: None,
+            count: None,
+            time_of_day: None,
+        }
+    }
+
+    #[test]
+    fn daily_keeps_time_of_day() {
+        let pills = rule(Frequency::Daily);
+        assert_eq!(
+            pills.occurrences(at(2025, 1, 30, 8), 3, UTC),
+            vec![at(2025, 1, 30, 8), at(2025, 1, 31, 8), at(2025, 2, 1, 8)]
+        );
+    }
+
+    #[test]
+    fn every_other_tuesday() {
+        let bins = Recurrence {
+            interval: 2,
+            by_weekday: vec![Weekday::Tue],
+            ..rule(Frequency::Weekly)
+        };
+        assert_eq!(
+            bins.occurrences(at(2025, 1, 7, 6), 3, UTC),
+            vec![at(2025, 1, 7, 6), at(2025, 1, 21, 6), at(2025, 2, 4, 6)]
+        );
+    }
+
+    #[test]
+    fn weekly_on_several_days() {
+        let gym = Recurrence {
+            by_weekday: vec![Weekday::Fri, Weekday::Mon, Weekday::Wed],
+            ..rule(Frequency::Weekly)
+        };
+        assert_eq!(
+            gym.occurrences(at(2025, 1, 8, 17), 4, UTC),
+            vec![at(2025, 1, 8, 17), at(2025, 1, 10, 17), at(2025, 1, 13, 17), at(2025, 1, 15, 17)]
+        );
+    }
+
+    #[test]
+    fn daily_on_weekdays_skips_weekend() {
+        let alarm = Recurrence {
+            by_weekday: vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+            ..rule(Frequency::Daily)
+        };
+        assert_eq!(alarm.advance(at(2025, 1, 10, 7), UTC).unwrap().0, at(2025, 1, 13, 7));
+    }
+
+    #[test]
+    fn monthly_skips_months_without_the_day() {
+        let rent = rule(Frequency::Monthly);
+        assert_eq!(
+            rent.occurrences(at(2025, 1, 31, 9), 3, UTC),
+            vec![at(2025, 1, 31, 9), at(2025, 3, 31, 9), at(2025, 5, 31, 9)]
+        );
+    }
+
+    #[test]
+    fn monthly_intervals_keep_going() {
+        let yearly = Recurrence { interval: 12, ..rule(Frequency::Monthly) };
+        assert_eq!(
+            yearly.occurrences(at(2024, 2, 29, 9), 3, UTC),
+            vec![at(2024, 2, 29, 9), at(2028, 2, 29, 9), at(2032, 2, 29, 9)]
+        );
+    }
+
+    #[test]
+    fn refuses_intervals_past_a_year() {
+        assert!(Recurrence { interval: 365, ..rule(Frequency::Daily) }.validate().is_ok());
+        assert_eq!(
+            Recurrence { interval: 366, ..rule(Frequency::Daily) }.validate(),
+            Err("A reminder can repeat at most every 365 days.".to_string())
+        );
+        assert!(Recurrence { interval: 53, ..rule(Frequency::Weekly) }.validate().is_err());
+        assert!(Recurrence { interval: 60, ..rule(Frequency::Monthly) }.validate().is_err());
+    }
+
+    #[test]
+    fn refuses_daily_rules_stuck_on_one_weekday() {
+        let fortnightly = Recurrence {
+            interval: 14,
+            by_weekday: vec![Weekday::Mon, Weekday::Fri],
+            ..rule(Frequency::Daily)
+        };
+        assert_eq!(
+            fortnightly.validate(),
+            Err("Every 14 days always falls on the same weekday. Make it a weekly reminder on that day instead.".to_string())
+        );
+        assert!(Recurrence { interval: 14, ..rule(Frequency::Daily) }.validate().is_ok());
+        assert!(Recurrence { interval: 3, ..fortnightly }.validate().is_ok());
+    }
+
+    #[test]
+    fn first_occurrence_lands_on_one_of_the_days() {
+        // 2025-01-07 is a Tuesday
+        let mondays = Recurrence { by_weekday: vec![Weekday::Mon], ..rule(Frequency::Weekly) };
+        let (first, pinned) = mondays.first_occurrence(helsinki(2025, 1, 7, 9, 0), Helsinki);
+        assert_eq!(first, helsinki(2025, 1, 13, 9, 0));
+        assert_eq!(pinned.to_string(), "FREQ=WEEKLY;BYDAY=MO;BYHOUR=9;BYMINUTE=0");
+
+        let (first, _) = mondays.first_occurrence(helsinki(2025, 1, 6, 9, 0), Helsinki);
+        assert_eq!(first, helsinki(2025, 1, 6, 9, 0));
+        let weekdays = Recurrence {
+            by_weekday: vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+            ..rule(Frequency::Daily)
+        };
+        let (first, _) = weekdays.first_occurrence(helsinki(2025, 1, 11, 7, 30), Helsinki);
+        assert_eq!(first, helsinki(2025, 1, 13, 7, 30));
+    }
+
+    #[test]
+    fn a_pinned_time_outlasts_a_snoozed_occurrence() {
+        let (first, pills) = rule(Frequency::Daily).first_occurrence(helsinki(2025, 1, 7, 8, 0), Helsinki);
+        assert_eq!(first, helsinki(2025, 1, 7, 8, 0));
+        // snoozed to 8:10, the next one is still at 8:00
+        let (next, _) = pills.advance(helsinki(2025, 1, 7, 8, 10), Helsinki).unwrap();
+        assert_eq!(next, helsinki(2025, 1, 8, 8, 0));
+    }
+
+    #[test]
+    fn huge_intervals_end_the_series_instead_of_overflowing() {
+        // stored before intervals were bounded, or written into the database by hand
+        for frequency in [Frequency::Daily, Frequency::Weekly, Frequency::Monthly] {
+            let absurd = Recurrence { interval: 4_000_000_000, ..rule(frequency) };
+            assert!(absurd.validate().is_err());
+            assert_eq!(absurd.advance(at(2025, 1, 1, 8), UTC), None, "{:?}", frequency);
+            assert_eq!(absurd.occurrences(at(2025, 1, 1, 8), 5, UTC), vec![at(2025, 1, 1, 8)]);
+        }
+        let mondays = Recurrence {
+            interval: 4_000_000_000,
+            by_weekday: vec![Weekday::Mon],
+            ..rule(Frequency::Weekly)
+        };
+        assert_eq!(mondays.advance(at(2025, 1, 6, 8), UTC), None);
+        let weekend = Recurrence { by_weekday: vec![Weekday::Sat], ..mondays.clone() };
+        assert_eq!(weekend.advance(at(2025, 1, 6, 8), UTC).unwrap().0, at(2025, 1, 11, 8));
+        let daily = Recurrence { frequency: Frequency::Daily, ..mondays };
+        assert_eq!(daily.advance(at(2025, 1, 6, 8), UTC), None);
+    }
+
+    #[test]
+    fn count_and_until_end_the_series() {
+        let three_times = Recurrence { count: Some(3), ..rule(Frequency::Daily) };
+        assert_eq!(three_times.occurrences(at(2025, 1, 1, 8), 10, UTC).len(), 3);
+        let (_, next) = three_times.advance(at(2025, 1, 1, 8), UTC).unwrap();
+        assert_eq!(next.count, Some(2));
+
+        let until = Recurrence { until: Some(at(2025, 1, 3, 8)), ..rule(Frequency::Daily) };
+        assert_eq!(
+            until.occurrences(at(2025, 1, 1, 8), 10, UTC),
+            vec![at(2025, 1, 1, 8), at(2025, 1, 2, 8), at(2025, 1, 3, 8)]
+        );
+    }
+
+    #[test]
+    fn rrule_round_trip() {
+        let bins = Recurrence {
+            interval: 2,
+            by_weekday: vec![Weekday::Tue, Weekday::Thu],
+            until: Some(at(2025, 6, 1, 0)),
+            count: Some(10),
+            ..rule(Frequency::Weekly)
+        };
+        let text = bins.to_string();
+        assert_eq!(text, "FREQ=WEEKLY;INTERVAL=2;BYDAY=TU,TH;UNTIL=20250601T000000Z;COUNT=10");
+        assert_eq!(text.parse::<Recurrence>().unwrap(), bins);
+        assert!("FREQ=YEARLY".parse::<Recurrence>().is_err());
+
+        let pills = Recurrence { time_of_day: NaiveTime::from_hms_opt(8, 30, 15), ..rule(Frequency::Daily) };
+        let text = pills.to_string();
+        assert_eq!(text, "FREQ=DAILY;BYHOUR=8;BYMINUTE=30;BYSECOND=15");
+        assert_eq!(text.parse::<Recurrence>().unwrap(), pills);
+        assert!("FREQ=DAILY;BYHOUR=25".parse::<Recurrence>().is_err());
+    }
+
+    #[test]
+    fn deserializes_tool_arguments() {
+        let parsed: Recurrence = serde_json::from_str(
+            r#"{"frequency": "weekly", "interval": 2, "by_weekday": ["tuesday"], "count": 4}"#,
+        )
+        .unwrap();
+        assert_eq!(parsed.to_string(), "FREQ=WEEKLY;INTERVAL=2;BYDAY=TU;COUNT=4");
+    }
+
+    fn helsinki(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Helsinki.with_ymd_and_hms(y, m, d, h, min, 0).earliest().unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn keeps_local_time_across_spring_forward() {
+        // clocks go from 03:00 to 04:00 on 2025-03-30 in Helsinki
+        let pills = rule(Frequency::Daily);
+        assert_eq!(
+            pills.occurrences(helsinki(2025, 3, 29, 8, 0), 3, Helsinki),
+            vec![helsinki(2025, 3, 29, 8, 0), helsinki(2025, 3, 30, 8, 0), helsinki(2025, 3, 31, 8, 0)]
+        );
+        assert_eq!(helsinki(2025, 3, 29, 8, 0), at(2025, 3, 29, 6));
+        assert_eq!(helsinki(2025, 3, 30, 8, 0), at(2025, 3, 30, 5));
+    }
+
+    #[test]
+    fn keeps_local_time_across_fall_back() {
+        // clocks go from 04:00 back to 03:00 on 2025-10-26 in Helsinki
+        let bins = Recurrence {
+            by_weekday: vec![Weekday::Sun],
+            ..rule(Frequency::Weekly)
+        };
+        assert_eq!(
+            bins.occurrences(helsinki(2025, 10, 19, 20, 0), 2, Helsinki),
+            vec![at(2025, 10, 19, 17), at(2025, 10, 26, 18)]
+        );
+    }
+
+    #[test]
+    fn times_inside_the_transition_hours() {
+        // 03:30 does not exist on the spring-forward night, it moves on to 04:30
+        let night = rule(Frequency::Daily);
+        let (skipped, _) = night.advance(helsinki(2025, 3, 29, 3, 30), Helsinki).unwrap();
+        assert_eq!(skipped, Utc.with_ymd_and_hms(2025, 3, 30, 1, 30, 0).unwrap());
+        // 03:30 happens twice on the fall-back night, the earlier one is taken
+        let (repeated, _) = night.advance(helsinki(2025, 10, 25, 3, 30), Helsinki).unwrap();
+        assert_eq!(repeated, Utc.with_ymd_and_hms(2025, 10, 26, 0, 30, 0).unwrap());
+        let (after, _) = night.advance(repeated, Helsinki).unwrap();
+        assert_eq!(after, Utc.with_ymd_and_hms(2025, 10, 27, 1, 30, 0).unwrap());
+    }
+
+    #[test]
+    fn returns_to_the_skipped_time_the_day_after() {
+        let night = rule(Frequency::Daily);
+        assert_eq!(
+            night.occurrences(helsinki(2025, 3, 29, 3, 30), 4, Helsinki),
+            vec![
+                helsinki(2025, 3, 29, 3, 30),
+                helsinki(2025, 3, 30, 4, 30),
+                helsinki(2025, 3, 31, 3, 30),
+                helsinki(2025, 4, 1, 3, 30),
+            ]
+        );
+        // as the scheduler sees it, one occurrence at a time through the stored rule
+        let (skipped, stored) = night.advance(helsinki(2025, 3, 29, 3, 30), Helsinki).unwrap();
+        let stored: Recurrence = stored.to_string().parse().unwrap();
+        let (after, _) = stored.advance(skipped, Helsinki).unwrap();
+        assert_eq!(after, helsinki(2025, 3, 31, 3, 30));
+    }
+
+    #[test]
+    fn weekdays_follow_the_local_date() {
+        // 00:30 on a Tuesday in Helsinki is still Monday in UTC
+        let tuesdays = Recurrence {
+            by_weekday: vec![Weekday::Tue],
+            ..rule(Frequency::Weekly)
+        };
+        assert_eq!(
+            tuesdays.advance(helsinki(2025, 1, 7, 0, 30), Helsinki).unwrap().0,
+            helsinki(2025, 1, 14, 0, 30)
+        );
+    }
+}
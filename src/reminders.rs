@@ -0,0 +1,151 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use crate::models::{Reminder, User};
+use crate::recurrence::Recurrence;
+
+
+pub fn create_reminder(
+    conn: &mut SqliteConnection,
+    user: &User,
+    text: &str,
+    due_at: DateTime<Utc>,
+    rule: Option<&Recurrence>,
+    tz: Tz,
+) -> Result<Reminder, diesel::result::Error> {
+    use crate::schema::reminders::dsl::*;
+
+    let mut new_reminder = Reminder::new(user.id.clone(), text.to_string(), due_at);
+    if let Some(rule) = rule {
+        let (first, rule) = rule.first_occurrence(due_at, tz);
+        new_reminder.remind_at = first.naive_utc();
+        new_reminder.recurrence = Some(rule.to_string());
+    }
+
+    diesel::insert_into(reminders)
+        .values(&new_reminder)
+        .execute(conn)?;
+
+    tracing::info!("Created reminder {} due {}", new_reminder.id, new_reminder.remind_at);
+    Ok(new_reminder)
+}
+
+pub fn list_reminders(
+    conn: &mut SqliteConnection,
+    user: &User,
+) -> Result<Vec<Reminder>, diesel::result::Error> {
+    use crate::schema::reminders::dsl::*;
+
+    reminders
+        .filter(user_id.eq(&user.id))
+        .load::<Reminder>(conn)
+}
+
+pub fn delete_all_reminders(
+    conn: &mut SqliteConnection,
+    user: &User,
+) -> Result<usize, diesel::result::Error> {
+    use crate::schema::reminders::dsl::*;
+
+    diesel::delete(reminders.filter(user_id.eq(&user.id))).execute(conn)
+}
+
+pub fn find_reminder(
+    conn: &mut SqliteConnection,
+    user: &User,
+    reminder_id: &str,
+) -> Result<Option<Reminder>, diesel::result::Error> {
+    use crate::schema::reminders::dsl::*;
+
+    reminders
+        .find(reminder_id)
+        .filter(user_id.eq(&user.id))
+        .first::<Reminder>(conn)
+        .optional()
+}
+
+pub fn delete_reminder(
+    conn: &mut SqliteConnection,
+    user: &User,
+    reminder_id: &str,
+) -> Result<Option<Reminder>, diesel::result::Error> {
+    use crate::schema::reminders::dsl::*;
+
+    let Some(reminder) = find_reminder(conn, user, reminder_id)? else {
+        return Ok(None);
+    };
+    diesel::delete(reminders.find(&reminder.id)).execute(conn)?;
+    Ok(Some(reminder))
+}
+
+pub fn update_reminder(
+    conn: &mut SqliteConnection,
+    user: &User,
+    reminder_id: &str,
+    new_message: Option<&str>,
+    new_remind_at: Option<DateTime<Utc>>,
+    tz: Tz,
+) -> Result<Option<Reminder>, diesel::result::Error> {
+    let Some(mut reminder) = find_reminder(conn, user, reminder_id)? else {
+        return Ok(None);
+    };
+    if let Some(new_message) = new_message {
+        reminder.message = new_message.to_string();
+    }
+    if let Some(new_remind_at) = new_remind_at {
+        reminder.remind_at = new_remind_at.naive_utc();
+        reminder.delivered_at = None;
+        reminder.delivery_attempts = 0;
+        reminder.retry_at = None;
+        // a repeating reminder moved to another time repeats at the new one
+        if let Some(rule) = reminder.recurrence() {
+            let time = new_remind_at.with_timezone(&tz).time();
+            reminder.recurrence = Some(Recurrence { time_of_day: Some(time), ..rule }.to_string());
+        }
+    }
+    save_reminder(conn, &reminder)?;
+    Ok(Some(reminder))
+}
+
+/// Moves the reminder to `until`, firing it again even if it already went off. A
+/// repeating reminder's later occurrences stay at the series' time of day.
+pub fn snooze_reminder(
+    conn: &mut SqliteConnection,
+    user: &User,
+    reminder_id: &str,
+    until: DateTime<Utc>,
+    tz: Tz,
+) -> Result<Option<Reminder>, diesel::result::Error> {
+    let Some(mut reminder) = find_reminder(conn, user, reminder_id)? else {
+        return Ok(None);
+    };
+    // set before times were pinned when a reminder is created
+    if let Some(rule) = reminder.recurrence() {
+        let time = reminder.into_datetime().with_timezone(&tz).time();
+        reminder.recurrence = Some(rule.pinned_to(time).to_string());
+    }
+    reminder.remind_at = until.naive_utc();
+    reminder.delivered_at = None;
+    reminder.delivery_attempts = 0;
+    reminder.retry_at = None;
+    save_reminder(conn, &reminder)?;
+    Ok(Some(reminder))
+}
+
+fn save_reminder(
+    conn: &mut SqliteConnection,
+    reminder: &Reminder,
+) -> Result<usize, diesel::result::Error> {
+    use crate::schema::reminders::dsl::*;
+
+    diesel::update(reminders.find(&reminder.id))
+        .set((
+            message.eq(&reminder.message),
+            remind_at.eq(reminder.remind_at),
+            delivered_at.eq(reminder.delivered_at),
+            recurrence.eq(&reminder.recurrence),
+            delivery_attempts.eq(reminder.delivery_attempts),
+            retry_at.eq(reminder.retry_at),
+        ))
+        .execute(conn)
+}
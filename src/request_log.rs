@@ -0,0 +1,334 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Instant;
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use serde_json::Value;
+use tracing::Instrument;
+use uuid::Uuid;
+use crate::config::Config;
+use crate::server::read_body;
+
+
+/// Header carrying the request ID, taken from the request when the caller sent one
+/// and echoed on the response.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+const REDACTED: &str = "[redacted]";
+
+/// Headers holding secrets or the caller's identity.
+const DEFAULT_REDACTED_HEADERS: &[&str] = &[
+    "authorization",
+    "cookie",
+    "set-cookie",
+    "x-vapi-secret",
+    "x-vapi-signature",
+];
+
+/// JSON fields holding what callers said or who they are: tool arguments carry
+/// reminder text and questions, `number` the phone number, and the call artifact
+/// repeats the conversation under `messagesOpenAIFormatted`.
+const DEFAULT_REDACTED_FIELDS: &[&str] = &[
+    "arguments",
+    "number",
+    "transcript",
+    "messages",
+    "messagesOpenAIFormatted",
+    "summary",
+];
+
+const DEFAULT_MAX_BODY: usize = 2048;
+
+/// Logs each request once, with secrets and private text taken out, inside a span
+/// carrying the request ID and the IDs of the tool calls in it, so everything the
+/// handlers log can be traced back to the request.
+#[derive(Clone)]
+pub struct RequestLogger {
+    redacted_headers: Arc<[String]>,
+    redacted_fields: Arc<[String]>,
+    max_body: usize,
+}
+
+impl Default for RequestLogger {
+    fn default() -> Self {
+        let owned = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        RequestLogger {
+            redacted_headers: owned(DEFAULT_REDACTED_HEADERS),
+            redacted_fields: owned(DEFAULT_REDACTED_FIELDS),
+            max_body: DEFAULT_MAX_BODY,
+        }
+    }
+}
+
+impl RequestLogger {
+    pub fn new() -> Self {
+        RequestLogger::default()
+    }
+
+    /// Also redacts these headers, matched case-insensitively.
+    pub fn with_redacted_headers(mut self, names: &[String]) -> Self {
+        let mut headers = self.redacted_headers.to_vec();
+        headers.extend(names.iter().map(|name| name.to_ascii_lowercase()));
+        self.redacted_headers = headers.into();
+        self
+    }
+
+    /// Also redacts these JSON fields, wherever they appear in the body.
+    pub fn with_redacted_fields(mut self, names: &[String]) -> Self {
+        let mut fields = self.redacted_fields.to_vec();
+        fields.extend(names.iter().cloned());
+        self.redacted_fields = fields.into();
+        self
+    }
+
+    /// Bodies are cut to this many bytes in the log.
+    pub fn with_max_body(mut self, max_body: usize) -> Self {
+        self.max_body = max_body;
+        self
+    }
+
+    /// The defaults plus the `[log]` redactions and body limit.
+    pub fn from_config(config: &Config) -> Self {
+        let logger = RequestLogger::new()
+            .with_redacted_headers(&config.log.redact_headers)
+            .with_redacted_fields(&config.log.redact_fields);
+        match config.log.max_body {
+            Some(max_body) => logger.with_max_body(max_body),
+            None => logger,
+        }
+    }
+
+    /// Headers as they may be logged.
+    pub fn headers(&self, headers: &HeaderMap) -> BTreeMap<String, String> {
+        headers
+            .iter()
+            .map(|(name, value)| {
+                let value = if self.redacted_headers.iter().any(|redacted| redacted == name.as_str()) {
+                    REDACTED.to_string()
+                } else {
+                    String::from_utf8_lossy(value.as_bytes()).into_owned()
+                };
+                (name.to_string(), value)
+            })
+            .collect()
+    }
+
+    /// The body as it may be logged: JSON with the redacted fields blanked out and
+    /// cut to `max_body`. Anything that isn't JSON can't be redacted, so only its
+    /// size is logged.
+    pub fn body(&self, body: &[u8]) -> String {
+        if body.is_empty() {
+            return String::new();
+        }
+        let Ok(mut json) = serde_json::from_slice::<Value>(body) else {
+            return format!("<{} bytes, not JSON>", body.len());
+        };
+        self.redact(&mut json);
+        let text = json.to_string();
+        if text.len() <= self.max_body {
+            return text;
+        }
+        let mut end = self.max_body;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!("{}… ({} bytes)", &text[..end], text.len())
+    }
+
+    fn redact(&self, json: &mut Value) {
+        match json {
+            Value::Object(fields) => {
+                for (name, value) in fields.iter_mut() {
+                    if self.redacted_fields.iter().any(|redacted| redacted == name) {
+                        *value = Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact(value);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact(item)),
+            _ => {}
+        }
+    }
+}
+
+/// IDs of the tool calls in a voice platform webhook, comma separated.
+fn tool_call_ids(body: &[u8]) -> Option<String> {
+    let json: Value = serde_json::from_slice(body).ok()?;
+    let ids: Vec<&str> = json["message"]["toolCalls"]
+        .as_array()?
+        .iter()
+        .filter_map(|call| call["id"].as_str())
+        .collect();
+    Some(ids.join(","))
+}
+
+/// Middleware logging the request and its outcome inside a span that everything
+/// the handler logs belongs to. Bodies it can't read in full are answered with what
+/// [`read_body`] answers, without reaching the handler.
+pub async fn log_request(State(logger): State<RequestLogger>, req: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let (parts, body) = req.into_parts();
+
+    let request_id = parts
+        .headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= 128)
+        .map_or_else(|| Uuid::new_v4().to_string(), str::to_string);
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %parts.method,
+        path = %parts.uri.path(),
+        tool_call_ids = tracing::field::Empty,
+    );
+
+    async {
+        let mut response = match read_body(body).await {
+            Ok(bytes) => {
+                if let Some(ids) = tool_call_ids(&bytes) {
+                    tracing::Span::current().record("tool_call_ids", ids.as_str());
+                }
+                tracing::info!(
+                    headers = ?logger.headers(&parts.headers),
+                    body = %logger.body(&bytes),
+                    "Request received"
+                );
+                next.run(Request::from_parts(parts, Body::from(bytes))).await
+            }
+            Err(refused) => {
+                tracing::warn!(headers = ?logger.headers(&parts.headers), "Request body refused");
+                refused
+            }
+        };
+        tracing::info!(
+            status = response.status().as_u16(),
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "Request handled"
+        );
+        if let Ok(id) = HeaderValue::from_str(&request_id) {
+            response.headers_mut().insert(REQUEST_ID_HEADER, id);
+        }
+        response
+    }
+    .instrument(span)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::middleware;
+    use axum::routing::post;
+    use axum::Router;
+    use http_body_util::BodyExt;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    #[test]
+    fn redacts_secret_headers() {
+        let logger = RequestLogger::new().with_redacted_headers(&["X-Api-Key".to_string()]);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-vapi-secret", "hunter2".parse().unwrap());
+        headers.insert("x-api-key", "abc".parse().unwrap());
+        headers.insert("content-type", "application/json".parse().unwrap());
+
+        let logged = logger.headers(&headers);
+        assert_eq!(logged["x-vapi-secret"], "[redacted]");
+        assert_eq!(logged["x-api-key"], "[redacted]");
+        assert_eq!(logged["content-type"], "application/json");
+    }
+
+    #[test]
+    fn redacts_private_fields_at_any_depth() {
+        let logger = RequestLogger::new();
+        let body = json!({
+            "message": {
+                "toolCalls": [{ "id": "call_1", "function": { "name": "StoreUserReminder", "arguments": { "message": "see the doctor about the rash" } } }],
+                "customer": { "number": "+358401234567" }
+            }
+        });
+
+        let logged = logger.body(body.to_string().as_bytes());
+        assert!(!logged.contains("rash"), "{}", logged);
+        assert!(!logged.contains("+358401234567"), "{}", logged);
+        assert!(logged.contains("StoreUserReminder"));
+        assert!(logged.contains("call_1"));
+        assert_eq!(logger.body(b"To=+358401234567&Body=hi"), "<24 bytes, not JSON>");
+    }
+
+    #[test]
+    fn keeps_the_conversation_out_of_a_real_webhook() {
+        let mut payload: Value = serde_json::from_str(include_str!("vapi-payload.json")).unwrap();
+        payload["message"]["call"]["customer"] = json!({ "number": "+358401234567" });
+        let logger = RequestLogger::new().with_max_body(usize::MAX);
+
+        let logged = logger.body(payload.to_string().as_bytes());
+        for private in ["can you get my reminders", "What's up?", "living in Tampere", "+358401234567"] {
+            assert!(!logged.contains(private), "{} in {}", private, logged);
+        }
+        assert!(logged.contains("GetUserReminders"));
+    }
+
+    #[test]
+    fn cuts_long_bodies() {
+        let logger = RequestLogger::new().with_max_body(20);
+        let body = json!({ "text": "ä".repeat(100) }).to_string();
+        let logged = logger.body(body.as_bytes());
+        assert!(logged.starts_with("{\"text\":\"äää"));
+        assert!(logged.ends_with(&format!("… ({} bytes)", body.len())));
+        assert!(logged.len() < 40);
+    }
+
+    #[test]
+    fn finds_the_tool_call_ids() {
+        let body = json!({ "message": { "toolCalls": [{ "id": "call_1" }, { "id": "call_2" }] } });
+        assert_eq!(tool_call_ids(body.to_string().as_bytes()).as_deref(), Some("call_1,call_2"));
+        assert_eq!(tool_call_ids(b"{}"), None);
+    }
+
+    #[tokio::test]
+    async fn tags_responses_with_the_request_id() {
+        let app = Router::new()
+            .route("/tool-call", post(|body: String| async move { body }))
+            .layer(middleware::from_fn_with_state(RequestLogger::new(), log_request));
+        let request = |id: Option<&str>| {
+            let mut request = Request::builder().uri("/tool-call").method("POST");
+            if let Some(id) = id {
+                request = request.header(REQUEST_ID_HEADER, id);
+            }
+            request.body(Body::from("{}")).unwrap()
+        };
+
+        let response = app.clone().oneshot(request(Some("abc-123"))).await.unwrap();
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "abc-123");
+        // the handler still gets the whole body
+        assert_eq!(&response.into_body().collect().await.unwrap().to_bytes()[..], b"{}");
+
+        let response = app.oneshot(request(None)).await.unwrap();
+        let id = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+        assert!(Uuid::parse_str(id).is_ok());
+    }
+
+    #[tokio::test]
+    async fn refuses_bodies_past_the_limit() {
+        let app = Router::new()
+            .route("/tool-call", post(|body: String| async move { body }))
+            .layer(middleware::from_fn_with_state(RequestLogger::new(), log_request));
+        let huge = vec![b' '; crate::server::MAX_BODY_BYTES + 1];
+        let request = Request::builder()
+            .uri("/tool-call")
+            .method("POST")
+            .header(REQUEST_ID_HEADER, "abc-123")
+            .body(Body::from(huge))
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "abc-123");
+    }
+}
@@ -0,0 +1,434 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::db::{self, Pool};
+use crate::delivery::{Delivery, Receipt};
+use crate::error::Error;
+use crate::models::{Reminder, User};
+use crate::recurrence::Recurrence;
+
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Deliveries tried for one occurrence before the scheduler gives up on it, so a
+/// number that can't be reached doesn't get called or texted every tick forever.
+const MAX_DELIVERY_ATTEMPTS: i32 = 5;
+/// Wait before trying a failed delivery again, doubled after every further failure.
+const RETRY_BACKOFF: TimeDelta = TimeDelta::minutes(1);
+
+/// Source of the current time, so the scheduler can be driven by a fake clock in tests.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Periodically scans for reminders whose `remind_at` has passed and hands them
+/// to the delivery channel, addressed to the user who set them.
+///
+/// A reminder is marked delivered only after the channel accepted it, so a restart
+/// never sends it twice. Repeating reminders are moved on to their next occurrence
+/// instead. A failed delivery is retried with a growing delay; after
+/// `MAX_DELIVERY_ATTEMPTS` a repeating reminder skips to its next occurrence and a
+/// one-off reminder is left undelivered.
+pub struct Scheduler {
+    pool: Pool,
+    delivery: Arc<dyn Delivery>,
+    clock: Arc<dyn Clock>,
+    poll_interval: Duration,
+    /// Zone repeating reminders follow for users who haven't set their own.
+    default_timezone: Tz,
+}
+
+impl Scheduler {
+    pub fn new(pool: Pool, delivery: Arc<dyn Delivery>) -> Self {
+        Scheduler {
+            pool,
+            delivery,
+            clock: Arc::new(SystemClock),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            default_timezone: Tz::UTC,
+        }
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn with_default_timezone(mut self, default_timezone: Tz) -> Self {
+        self.default_timezone = default_timezone;
+        self
+    }
+
+    /// Runs forever, firing due reminders every `poll_interval`.
+    pub async fn run(self) {
+        tracing::info!("Reminder scheduler polling every {:?}", self.poll_interval);
+        let mut ticker = tokio::time::interval(self.poll_interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.tick().await {
+                tracing::error!("Reminder scheduler tick failed: {}", e);
+            }
+        }
+    }
+
+    /// Delivers every due reminder once and returns how many were delivered.
+    pub async fn tick(&self) -> Result<usize, Error> {
+        let now = self.clock.now();
+        let due = db::run(&self.pool, move |conn| due_reminders(conn, now)).await?;
+
+        let mut delivered = 0;
+        for (reminder, user) in due {
+            match self.delivery.deliver(&user, &reminder).await {
+                Ok(receipt) => {
+                    let tz = user.timezone(self.default_timezone);
+                    let next = next_occurrence(&reminder, tz, now);
+                    db::run(&self.pool, move |conn| match next {
+                        Some((next, rule)) => reschedule(conn, &reminder.id, next, &rule, &receipt),
+                        None => mark_delivered(conn, &reminder.id, now, &receipt),
+                    })
+                    .await?;
+                    delivered += 1;
+                }
+                Err(e) => {
+                    let attempts = reminder.delivery_attempts + 1;
+                    if attempts < MAX_DELIVERY_ATTEMPTS {
+                        let retry = now + RETRY_BACKOFF * 2i32.pow(attempts as u32 - 1);
+                        tracing::warn!("Could not deliver reminder {}, trying again at {}: {}", reminder.id, retry, e);
+                        db::run(&self.pool, move |conn| record_failure(conn, &reminder.id, attempts, Some(retry))).await?;
+                        continue;
+                    }
+                    tracing::error!("Giving up on reminder {} after {} attempts: {}", reminder.id, attempts, e);
+                    let tz = user.timezone(self.default_timezone);
+                    let next = next_occurrence(&reminder, tz, now);
+                    db::run(&self.pool, move |conn| match next {
+                        Some((next, rule)) => reschedule(conn, &reminder.id, next, &rule, &Receipt::default()),
+                        None => record_failure(conn, &reminder.id, attempts, None),
+                    })
+                    .await?;
+                }
+            }
+        }
+        Ok(delivered)
+    }
+}
+
+fn due_reminders(
+    conn: &mut SqliteConnection,
+    now: DateTime<Utc>,
+) -> Result<Vec<(Reminder, User)>, diesel::result::Error> {
+    use crate::schema::reminders::dsl::*;
+    use crate::schema::users;
+
+    reminders
+        .inner_join(users::table)
+        .filter(delivered_at.is_null())
+        .filter(remind_at.le(now.naive_utc()))
+        .filter(delivery_attempts.lt(MAX_DELIVERY_ATTEMPTS))
+        .filter(retry_at.is_null().or(retry_at.le(now.naive_utc())))
+        .load::<(Reminder, User)>(conn)
+}
+
+/// The first occurrence after `now`, skipping any that were missed while the
+/// server was down rather than firing them all at once.
+fn next_occurrence(reminder: &Reminder, tz: Tz, now: DateTime<Utc>) -> Option<(DateTime<Utc>, Recurrence)> {
+    let mut next = reminder.recurrence()?.advance(reminder.into_datetime(), tz)?;
+    while next.0 <= now {
+        next = next.1.advance(next.0, tz)?;
+    }
+    Some(next)
+}
+
+fn reschedule(
+    conn: &mut SqliteConnection,
+    reminder_id: &str,
+    next: DateTime<Utc>,
+    rule: &Recurrence,
+    receipt: &Receipt,
+) -> Result<usize, diesel::result::Error> {
+    use crate::schema::reminders::dsl::*;
+
+    diesel::update(reminders.find(reminder_id))
+        .set((
+            remind_at.eq(next.naive_utc()),
+            recurrence.eq(rule.to_string()),
+            call_id.eq(&receipt.call_id),
+            call_status.eq(&receipt.call_status),
+            delivery_attempts.eq(0),
+            retry_at.eq(None::<chrono::NaiveDateTime>),
+        ))
+        .execute(conn)
+}
+
+/// Counts a failed delivery and sets when to try again; without a retry time the
+/// occurrence is not tried again.
+fn record_failure(
+    conn: &mut SqliteConnection,
+    reminder_id: &str,
+    attempts: i32,
+    retry: Option<DateTime<Utc>>,
+) -> Result<usize, diesel::result::Error> {
+    use crate::schema::reminders::dsl::*;
+
+    diesel::update(reminders.find(reminder_id))
+        .set((delivery_attempts.eq(attempts), retry_at.eq(retry.map(|at| at.naive_utc()))))
+        .execute(conn)
+}
+
+fn mark_delivered(
+    conn: &mut SqliteConnection,
+    reminder_id: &str,
+    now: DateTime<Utc>,
+    receipt: &Receipt,
+) -> Result<usize, diesel::result::Error> {
+    use crate::schema::reminders::dsl::*;
+
+    diesel::update(reminders.find(reminder_id))
+        .set((
+            delivered_at.eq(now.naive_utc()),
+            call_id.eq(&receipt.call_id),
+            call_status.eq(&receipt.call_status),
+        ))
+        .execute(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delivery::DeliveryError;
+    use async_trait::async_trait;
+    use chrono::TimeZone;
+    use std::sync::Mutex;
+
+    struct FakeClock(Mutex<DateTime<Utc>>);
+
+    impl FakeClock {
+        fn at(now: DateTime<Utc>) -> Arc<Self> {
+            Arc::new(FakeClock(Mutex::new(now)))
+        }
+
+        fn set(&self, now: DateTime<Utc>) {
+            *self.0.lock().unwrap() = now;
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> DateTime<Utc> {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    #[derive(Default)]
+    struct FakeDelivery {
+        sent: Mutex<Vec<String>>,
+        failing: Mutex<bool>,
+    }
+
+    #[async_trait]
+    impl Delivery for FakeDelivery {
+        async fn deliver(&self, _user: &User, reminder: &Reminder) -> Result<Receipt, DeliveryError> {
+            if *self.failing.lock().unwrap() {
+                return Err(DeliveryError("sink unavailable".to_string()));
+            }
+            self.sent.lock().unwrap().push(reminder.message.clone());
+            Ok(Receipt::default())
+        }
+    }
+
+    fn insert(conn: &Pool, owner: Option<&User>, message: &str, remind_at: &str) -> Reminder {
+        use crate::schema::reminders::dsl::reminders;
+
+        let remind_at = DateTime::parse_from_rfc3339(remind_at).unwrap().with_timezone(&Utc);
+        let mut reminder = Reminder::new(String::new(), message.to_string(), remind_at);
+        reminder.user_id = owner.map(|user| user.id.clone());
+        diesel::insert_into(reminders)
+            .values(&reminder)
+            .execute(&mut *conn.get().unwrap())
+            .unwrap();
+        reminder
+    }
+
+    fn setup() -> (Pool, Arc<FakeClock>, Arc<FakeDelivery>) {
+        let conn = crate::test_pool();
+        let user = crate::users::find_or_create_by_phone(&mut conn.get().unwrap(), "+358401234567").unwrap();
+        insert(&conn, Some(&user), "dentist", "2025-01-10T09:00:00+00:00");
+        insert(&conn, Some(&user), "buy milk", "2025-01-10T17:30:00+02:00");
+        insert(&conn, Some(&user), "call mom", "2025-01-11T12:00:00+00:00");
+        // left over from before reminders had owners, nobody to send it to
+        insert(&conn, None, "orphan", "2025-01-09T12:00:00+00:00");
+        let clock = FakeClock::at(Utc.with_ymd_and_hms(2025, 1, 10, 16, 0, 0).unwrap());
+        (conn, clock, Arc::new(FakeDelivery::default()))
+    }
+
+    #[tokio::test]
+    async fn delivers_only_due_reminders() {
+        let (conn, clock, delivery) = setup();
+        let scheduler = Scheduler::new(conn, delivery.clone()).with_clock(clock);
+
+        assert_eq!(scheduler.tick().await.unwrap(), 2);
+        let mut sent = delivery.sent.lock().unwrap().clone();
+        sent.sort();
+        assert_eq!(sent, vec!["buy milk", "dentist"]);
+    }
+
+    #[tokio::test]
+    async fn does_not_redeliver_after_restart() {
+        let (conn, clock, delivery) = setup();
+        let scheduler = Scheduler::new(conn.clone(), delivery.clone()).with_clock(clock.clone());
+        scheduler.tick().await.unwrap();
+
+        let restarted = Scheduler::new(conn, delivery.clone()).with_clock(clock.clone());
+        assert_eq!(restarted.tick().await.unwrap(), 0);
+
+        clock.set(Utc.with_ymd_and_hms(2025, 1, 11, 12, 0, 0).unwrap());
+        assert_eq!(restarted.tick().await.unwrap(), 1);
+        assert_eq!(delivery.sent.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_failed_deliveries() {
+        let (conn, clock, delivery) = setup();
+        let scheduler = Scheduler::new(conn, delivery.clone()).with_clock(clock.clone());
+
+        *delivery.failing.lock().unwrap() = true;
+        assert_eq!(scheduler.tick().await.unwrap(), 0);
+
+        *delivery.failing.lock().unwrap() = false;
+        // not before the backoff is over
+        assert_eq!(scheduler.tick().await.unwrap(), 0);
+        clock.set(Utc.with_ymd_and_hms(2025, 1, 10, 16, 1, 0).unwrap());
+        assert_eq!(scheduler.tick().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_deliveries_that_keep_failing() {
+        use crate::schema::reminders::dsl::*;
+
+        let (conn, clock, delivery) = setup();
+        let user = crate::users::find_by_phone(&mut conn.get().unwrap(), "+358401234567").unwrap().unwrap();
+        let pills = insert(&conn, Some(&user), "take pills", "2025-01-10T08:00:00+00:00");
+        diesel::update(reminders.find(&pills.id))
+            .set(recurrence.eq("FREQ=DAILY"))
+            .execute(&mut *conn.get().unwrap())
+            .unwrap();
+        let scheduler = Scheduler::new(conn.clone(), delivery.clone()).with_clock(clock.clone());
+
+        *delivery.failing.lock().unwrap() = true;
+        // retried after 1, 2, 4 and 8 minutes, then never again
+        let mut at = clock.now();
+        for wait in [0, 1, 2, 4, 8, 16] {
+            at += TimeDelta::minutes(wait);
+            clock.set(at);
+            scheduler.tick().await.unwrap();
+        }
+        let stored = |reminder_id: &str| reminders.find(reminder_id).first::<Reminder>(&mut *conn.get().unwrap()).unwrap();
+        let dentist = reminders
+            .filter(message.eq("dentist"))
+            .first::<Reminder>(&mut *conn.get().unwrap())
+            .unwrap();
+        assert_eq!(stored(&dentist.id).delivery_attempts, MAX_DELIVERY_ATTEMPTS);
+        assert_eq!(stored(&dentist.id).delivered_at, None);
+
+        // the repeating one moves on to tomorrow and starts counting again
+        let pills = stored(&pills.id);
+        assert_eq!(pills.into_datetime(), Utc.with_ymd_and_hms(2025, 1, 11, 8, 0, 0).unwrap());
+        assert_eq!(pills.delivery_attempts, 0);
+        assert_eq!(pills.retry_at, None);
+
+        *delivery.failing.lock().unwrap() = false;
+        clock.set(at + TimeDelta::days(1));
+        assert_eq!(scheduler.tick().await.unwrap(), 2);
+        let mut sent = delivery.sent.lock().unwrap().clone();
+        sent.sort();
+        assert_eq!(sent, ["call mom", "take pills"]);
+    }
+
+    #[tokio::test]
+    async fn moves_recurring_reminders_to_next_occurrence() {
+        use crate::schema::reminders::dsl::*;
+
+        let conn = crate::test_pool();
+        let user = crate::users::find_or_create_by_phone(&mut conn.get().unwrap(), "+358401234567").unwrap();
+        let pills = insert(&conn, Some(&user), "take pills", "2025-01-08T08:00:00+00:00");
+        diesel::update(reminders.find(&pills.id))
+            .set(recurrence.eq("FREQ=DAILY;COUNT=5"))
+            .execute(&mut *conn.get().unwrap())
+            .unwrap();
+
+        // the server was down for two days, so only one catch-up delivery is sent
+        let clock = FakeClock::at(Utc.with_ymd_and_hms(2025, 1, 10, 9, 0, 0).unwrap());
+        let delivery = Arc::new(FakeDelivery::default());
+        let scheduler = Scheduler::new(conn.clone(), delivery.clone()).with_clock(clock);
+        assert_eq!(scheduler.tick().await.unwrap(), 1);
+        assert_eq!(scheduler.tick().await.unwrap(), 0);
+
+        let stored = reminders.find(&pills.id).first::<Reminder>(&mut *conn.get().unwrap()).unwrap();
+        assert_eq!(stored.into_datetime(), Utc.with_ymd_and_hms(2025, 1, 11, 8, 0, 0).unwrap());
+        assert_eq!(stored.recurrence.as_deref(), Some("FREQ=DAILY;BYHOUR=8;BYMINUTE=0;COUNT=2"));
+        assert_eq!(stored.delivered_at, None);
+    }
+
+    #[tokio::test]
+    async fn recurring_reminders_follow_the_users_timezone() {
+        use crate::schema::reminders::dsl::*;
+        use crate::schema::users::dsl::{timezone, users};
+
+        let conn = crate::test_pool();
+        let user = crate::users::find_or_create_by_phone(&mut conn.get().unwrap(), "+358401234567").unwrap();
+        diesel::update(users.find(&user.id))
+            .set(timezone.eq("Europe/Helsinki"))
+            .execute(&mut *conn.get().unwrap())
+            .unwrap();
+        // 8:00 in Helsinki the day before clocks spring forward
+        let pills = insert(&conn, Some(&user), "take pills", "2025-03-29T08:00:00+02:00");
+        diesel::update(reminders.find(&pills.id))
+            .set(recurrence.eq("FREQ=DAILY"))
+            .execute(&mut *conn.get().unwrap())
+            .unwrap();
+
+        let clock = FakeClock::at(Utc.with_ymd_and_hms(2025, 3, 29, 6, 0, 0).unwrap());
+        let scheduler = Scheduler::new(conn.clone(), Arc::new(FakeDelivery::default()))
+            .with_clock(clock)
+            .with_default_timezone(Tz::UTC);
+        assert_eq!(scheduler.tick().await.unwrap(), 1);
+
+        let stored = reminders.find(&pills.id).first::<Reminder>(&mut *conn.get().unwrap()).unwrap();
+        assert_eq!(stored.into_datetime(), Utc.with_ymd_and_hms(2025, 3, 30, 5, 0, 0).unwrap());
+    }
+
+    #[tokio::test]
+    async fn ends_series_that_cannot_go_on() {
+        use crate::schema::reminders::dsl::*;
+
+        let conn = crate::test_pool();
+        let user = crate::users::find_or_create_by_phone(&mut conn.get().unwrap(), "+358401234567").unwrap();
+        let pills = insert(&conn, Some(&user), "take pills", "2025-01-10T08:00:00+00:00");
+        // next occurrence would be past the end of the calendar
+        diesel::update(reminders.find(&pills.id))
+            .set(recurrence.eq("FREQ=DAILY;INTERVAL=4000000000"))
+            .execute(&mut *conn.get().unwrap())
+            .unwrap();
+
+        let clock = FakeClock::at(Utc.with_ymd_and_hms(2025, 1, 10, 9, 0, 0).unwrap());
+        let scheduler = Scheduler::new(conn.clone(), Arc::new(FakeDelivery::default())).with_clock(clock);
+        assert_eq!(scheduler.tick().await.unwrap(), 1);
+        assert_eq!(scheduler.tick().await.unwrap(), 0);
+
+        let stored = reminders.find(&pills.id).first::<Reminder>(&mut *conn.get().unwrap()).unwrap();
+        assert_eq!(stored.into_datetime(), Utc.with_ymd_and_hms(2025, 1, 10, 8, 0, 0).unwrap());
+        assert!(stored.delivered_at.is_some());
+    }
+}
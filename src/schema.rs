@@ -1,9 +1,45 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    answer_cache (question, locale) {
+        question -> Text,
+        locale -> Text,
+        answer -> Text,
+        citations -> Text,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     reminders (id) {
         id -> Text,
         message -> Text,
-        remind_at -> Text,
+        remind_at -> Timestamp,
+        delivered_at -> Nullable<Timestamp>,
+        call_id -> Nullable<Text>,
+        call_status -> Nullable<Text>,
+        user_id -> Nullable<Text>,
+        recurrence -> Nullable<Text>,
+        delivery_attempts -> Integer,
+        retry_at -> Nullable<Timestamp>,
     }
 }
+
+diesel::table! {
+    users (id) {
+        id -> Text,
+        phone_number -> Text,
+        created_at -> Timestamp,
+        timezone -> Nullable<Text>,
+        answer_provider -> Nullable<Text>,
+    }
+}
+
+diesel::joinable!(reminders -> users (user_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    answer_cache,
+    reminders,
+    users,
+);
@@ -0,0 +1,234 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    routing::{get, post},
+    response::{IntoResponse, Json, Response},
+    http::StatusCode,
+    middleware,
+    Router,
+};
+use axum::debug_handler;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use http_body_util::LengthLimitError;
+use serde::Deserialize;
+use serde_json::Value;
+use crate::answers::AnswerProviders;
+use crate::calls::{self, CallDelivery, CallEventRequest};
+use crate::config::{Config, DeliveryChannel};
+use crate::db::{self, Pool};
+use crate::delivery::{Delivery, LogDelivery};
+use crate::error::Error;
+use crate::models::{ResponseWrapper, ToolCallResult};
+use crate::request_log::{log_request, RequestLogger};
+use crate::sms::{self, SmsDelivery, SmsSender};
+use crate::tools::{ToolContext, ToolDefinition, ToolRegistry};
+use crate::users;
+use crate::webhook_auth::{verify_webhook, WebhookAuth};
+
+
+/// Largest request body the server reads. The voice platform's webhooks are a few
+/// kilobytes, even with a long transcript attached.
+pub const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// What the handlers share, built once from the configuration.
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: Pool,
+    pub sms: Arc<dyn SmsSender>,
+    pub answers: AnswerProviders,
+    pub tools: ToolRegistry,
+    pub config: Arc<Config>,
+}
+
+impl AppState {
+    pub fn from_config(config: Arc<Config>, pool: Pool) -> Self {
+        AppState {
+            pool,
+            sms: sms::sender_from_config(&config),
+            answers: AnswerProviders::from_config(&config),
+            tools: ToolRegistry::from_config(&config),
+            config,
+        }
+    }
+
+    /// The channel `reminders.delivery` names; the configuration is checked for
+    /// calls having their settings before this runs.
+    pub fn reminder_delivery(&self) -> Arc<dyn Delivery> {
+        match self.config.reminders.delivery {
+            DeliveryChannel::Log => Arc::new(LogDelivery),
+            DeliveryChannel::Call => Arc::new(CallDelivery::from_config(&self.config).expect("vapi settings are validated")),
+            DeliveryChannel::Sms => Arc::new(SmsDelivery::new(self.sms.clone())),
+        }
+    }
+}
+
+/// The webhooks the voice platform calls, behind the webhook check, and the tool
+/// definitions, all logged through the request logger.
+pub fn router(state: AppState) -> Router {
+    let auth = WebhookAuth::from_config(&state.config);
+    let logger = RequestLogger::from_config(&state.config);
+    Router::new()
+        .route("/tool-call", post(handle_tool_call))
+        .route("/call-events", post(handle_call_event))
+        // only the voice platform may call the webhooks above
+        .route_layer(middleware::from_fn_with_state(auth, verify_webhook))
+        .route("/tools", get(tool_definitions))
+        .layer(middleware::from_fn_with_state(logger, log_request))
+        .with_state(state)
+}
+
+/// Reads the whole body for middleware that has to see it before the handler does.
+/// Fails with 413 past [`MAX_BODY_BYTES`] and with 400 when the client breaks off.
+pub(crate) async fn read_body(body: Body) -> Result<Bytes, Response> {
+    axum::body::to_bytes(body, MAX_BODY_BYTES).await.map_err(|e| {
+        let e = e.into_inner();
+        if e.is::<LengthLimitError>() {
+            (StatusCode::PAYLOAD_TOO_LARGE, "request body too large").into_response()
+        } else {
+            tracing::warn!("Could not read the request body: {}", e);
+            (StatusCode::BAD_REQUEST, "could not read the request body").into_response()
+        }
+    })
+}
+
+/// The enabled tools, as the voice assistant should be configured with them.
+async fn tool_definitions(State(state): State<AppState>) -> Json<Vec<ToolDefinition>> {
+    Json(state.tools.definitions(state.config.server.public_url.as_deref()))
+}
+
+#[derive(Deserialize, Debug)]
+struct ToolCallRequest {
+    message: ToolCallMessage,
+}
+
+#[derive(Deserialize, Debug)]
+struct ToolCallMessage {
+    /// When the message was sent, in milliseconds since the epoch.
+    timestamp: Option<i64>,
+    #[serde(rename = "toolCalls")]
+    tool_calls: Vec<ToolCall>,
+    call: Option<CallMetadata>,
+    customer: Option<Customer>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CallMetadata {
+    customer: Option<Customer>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Customer {
+    number: Option<String>,
+}
+
+impl ToolCallMessage {
+    /// The moment the caller spoke, which relative times like "in 20 minutes" count from.
+    fn sent_at(&self) -> DateTime<Utc> {
+        self.timestamp
+            .and_then(DateTime::from_timestamp_millis)
+            .unwrap_or_else(Utc::now)
+    }
+
+    /// Phone number of the caller, absent for web calls.
+    fn customer_number(&self) -> Option<&str> {
+        self.call
+            .as_ref()
+            .and_then(|call| call.customer.as_ref())
+            .or(self.customer.as_ref())
+            .and_then(|customer| customer.number.as_deref())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ToolCall {
+    id: String,
+    function: FunctionCall,
+}
+
+#[derive(Deserialize, Debug)]
+struct FunctionCall {
+    name: String,
+    arguments: Value,
+}
+
+
+#[debug_handler]
+async fn handle_tool_call(
+    State(state): State<AppState>,
+    Json(payload): Json<ToolCallRequest>,
+) -> Json<ResponseWrapper> {
+    tracing::info!("Handling tool call");
+
+    // the voice platform expects a result for every call, so even a failed lookup
+    // is reported per call rather than failing the whole request
+    let user = match payload.message.customer_number() {
+        Some(number) => {
+            let number = number.to_string();
+            db::run(&state.pool, move |conn| users::find_or_create_by_phone(conn, &number))
+                .await
+                .map(Some)
+        }
+        None => Ok(None),
+    };
+    let sent_at = payload.message.sent_at();
+
+    let tool_calls = payload.message.tool_calls;
+    let results: Vec<ToolCallResult> = match user {
+        Ok(user) => {
+            let ctx = ToolContext {
+                pool: state.pool.clone(),
+                sms: state.sms.clone(),
+                answers: state.answers.clone(),
+                default_timezone: state.config.server.default_timezone,
+                user,
+                sent_at,
+            };
+            let names: Vec<&str> = tool_calls.iter().map(|call| call.function.name.as_str()).collect();
+            tracing::info!("Handling tool calls: {}", names.join(", "));
+            let (ids, calls): (Vec<_>, Vec<_>) = tool_calls
+                .into_iter()
+                .map(|tool_call| (tool_call.id, (tool_call.function.name, tool_call.function.arguments)))
+                .unzip();
+            let outcomes = state.tools.call_batch(&ctx, calls, Duration::from_millis(state.config.server.tool_deadline_ms)).await;
+            ids.into_iter()
+                .zip(outcomes)
+                .map(|(id, outcome)| {
+                    if let Err(e) = &outcome {
+                        tracing::error!("Tool call {} failed: {}", id, e);
+                    }
+                    ToolCallResult::new(id, outcome)
+                })
+                .collect()
+        }
+        Err(e) => {
+            tracing::error!("Could not look up the caller: {}", e);
+            tool_calls
+                .into_iter()
+                .map(|tool_call| ToolCallResult::failed(tool_call.id, &e))
+                .collect()
+        }
+    };
+
+    tracing::info!("Returning {} results", results.len());
+    Json(ResponseWrapper { results })
+}
+
+
+/// Records how an outbound reminder call went.
+async fn handle_call_event(
+    State(state): State<AppState>,
+    Json(payload): Json<CallEventRequest>,
+) -> Result<StatusCode, Error> {
+    let event = payload.message;
+    let (Some(call), Some(outcome)) = (&event.call, event.outcome()) else {
+        tracing::debug!("Ignoring {} event", event.kind);
+        return Ok(StatusCode::OK);
+    };
+
+    tracing::info!("Call {} {}: {}", call.id, event.kind, outcome);
+    let (call_id, outcome) = (call.id.clone(), outcome.to_string());
+    db::run(&state.pool, move |conn| calls::record_call_status(conn, &call_id, &outcome)).await?;
+    Ok(StatusCode::OK)
+}
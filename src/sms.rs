@@ -0,0 +1,153 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use crate::config::Config;
+use crate::delivery::{Delivery, DeliveryError, Receipt};
+use crate::models::{Reminder, User};
+
+
+pub const TWILIO_BASE_URL: &str = "https://api.twilio.com";
+/// Twilio rejects message bodies longer than this.
+const MAX_SMS_LEN: usize = 1600;
+
+#[async_trait]
+pub trait SmsSender: Send + Sync {
+    async fn send(&self, to: &str, body: &str) -> Result<(), DeliveryError>;
+}
+
+/// Sends through the Twilio Messages REST API, or anything speaking the same protocol.
+pub struct TwilioSms {
+    client: reqwest::Client,
+    base_url: String,
+    account_sid: String,
+    auth_token: String,
+    from: String,
+}
+
+impl TwilioSms {
+    pub fn new(base_url: &str, account_sid: &str, auth_token: &str, from: &str) -> Self {
+        TwilioSms {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            account_sid: account_sid.to_string(),
+            auth_token: auth_token.to_string(),
+            from: from.to_string(),
+        }
+    }
+
+    /// Twilio with the `[twilio]` credentials, when all of them are there.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let twilio = &config.twilio;
+        Some(TwilioSms::new(
+            &twilio.base_url,
+            twilio.account_sid.as_deref()?,
+            twilio.auth_token.as_deref()?,
+            twilio.from_number.as_deref()?,
+        ))
+    }
+}
+
+#[async_trait]
+impl SmsSender for TwilioSms {
+    async fn send(&self, to: &str, body: &str) -> Result<(), DeliveryError> {
+        let url = format!("{}/2010-04-01/Accounts/{}/Messages.json", self.base_url, self.account_sid);
+        let body: String = body.chars().take(MAX_SMS_LEN).collect();
+
+        let response = self.client
+            .post(url)
+            .basic_auth(&self.account_sid, Some(&self.auth_token))
+            .form(&[("To", to), ("From", self.from.as_str()), ("Body", body.as_str())])
+            .send()
+            .await
+            .map_err(|e| DeliveryError(e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(DeliveryError(format!("SMS gateway returned {}: {}", status, text)));
+        }
+        tracing::info!("Sent SMS to {}", to);
+        Ok(())
+    }
+}
+
+/// Only logs the message, for development without an SMS account.
+pub struct LogSms;
+
+#[async_trait]
+impl SmsSender for LogSms {
+    async fn send(&self, to: &str, body: &str) -> Result<(), DeliveryError> {
+        tracing::info!("SMS to {}: {}", to, body);
+        Ok(())
+    }
+}
+
+/// Twilio when its credentials are configured, otherwise the log.
+pub fn sender_from_config(config: &Config) -> Arc<dyn SmsSender> {
+    match TwilioSms::from_config(config) {
+        Some(twilio) => Arc::new(twilio),
+        None => {
+            tracing::warn!("Twilio is not configured, SMS will only be logged");
+            Arc::new(LogSms)
+        }
+    }
+}
+
+/// Delivers reminders as a text message to the user's phone.
+pub struct SmsDelivery {
+    sender: Arc<dyn SmsSender>,
+}
+
+impl SmsDelivery {
+    pub fn new(sender: Arc<dyn SmsSender>) -> Self {
+        SmsDelivery { sender }
+    }
+}
+
+#[async_trait]
+impl Delivery for SmsDelivery {
+    async fn deliver(&self, user: &User, reminder: &Reminder) -> Result<Receipt, DeliveryError> {
+        self.sender
+            .send(&user.phone_number, &format!("Reminder: {}", reminder.message))
+            .await?;
+        Ok(Receipt::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{basic_auth, body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn twilio_posts_message_form() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/2010-04-01/Accounts/AC123/Messages.json"))
+            .and(basic_auth("AC123", "secret"))
+            .and(body_string_contains("To=%2B358401234567"))
+            .and(body_string_contains("Body=Reminder%3A+dentist"))
+            .respond_with(ResponseTemplate::new(201).set_body_string(r#"{"sid": "SM1"}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let sms = Arc::new(TwilioSms::new(&server.uri(), "AC123", "secret", "+358000000000"));
+        let user = User::new("+358401234567".to_string());
+        let reminder = Reminder::new(user.id.clone(), "dentist".to_string(), chrono::Utc::now());
+        SmsDelivery::new(sms).deliver(&user, &reminder).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn twilio_error_status_fails_delivery() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("invalid To number"))
+            .mount(&server)
+            .await;
+
+        let sms = TwilioSms::new(&server.uri(), "AC123", "secret", "+358000000000");
+        let err = sms.send("nope", "hello").await.unwrap_err();
+        assert!(err.to_string().contains("invalid To number"));
+    }
+}
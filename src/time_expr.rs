@@ -0,0 +1,675 @@
+//! Spoken time expressions such as "in 20 minutes", "tomorrow at 9" or "next Friday
+//! morning", resolved against the moment the caller said them.
+//!
+//! The parser is deliberately strict: every word has to be understood, so a phrase
+//! like "after the meeting" is rejected instead of being guessed at.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use crate::times::localize;
+
+
+/// Words that carry no meaning of their own in a time expression.
+const FILLERS: &[&str] = &["at", "on", "the", "of", "this", "coming", "around", "about", "o'clock", "oclock", "by"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Day {
+    Today,
+    Tomorrow,
+    Overmorrow,
+    Weekday { day: Weekday, next: bool },
+    NextWeek,
+    InDays(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DayPart {
+    Morning,
+    Noon,
+    Afternoon,
+    Evening,
+    Night,
+    Midnight,
+}
+
+impl DayPart {
+    fn default_time(self) -> (u32, u32) {
+        match self {
+            DayPart::Morning => (9, 0),
+            DayPart::Noon => (12, 0),
+            DayPart::Afternoon => (15, 0),
+            DayPart::Evening => (18, 0),
+            DayPart::Night => (21, 0),
+            DayPart::Midnight => (0, 0),
+        }
+    }
+
+    fn is_after_noon(self) -> bool {
+        matches!(self, DayPart::Afternoon | DayPart::Evening | DayPart::Night)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Meridiem {
+    Am,
+    Pm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ClockTime {
+    /// As spoken, so "quarter to 1 pm" has 1 here
+    hour: u32,
+    minute: u32,
+    meridiem: Option<Meridiem>,
+    /// "quarter to": the minutes are before `hour`, not after it
+    to_hour: bool,
+}
+
+#[derive(Debug, Default)]
+struct Expression {
+    offset: Option<Duration>,
+    day: Option<Day>,
+    part: Option<DayPart>,
+    time: Option<ClockTime>,
+}
+
+impl Expression {
+    /// Only fillers, e.g. "at the"
+    fn is_empty(&self) -> bool {
+        self.offset.is_none() && self.day.is_none() && self.part.is_none() && self.time.is_none()
+    }
+}
+
+/// Resolves `input` said at `reference` by someone in `tz`, or `None` if any part of
+/// it isn't understood.
+pub fn parse(input: &str, reference: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+    let tokens = tokenize(input);
+    if tokens.is_empty() {
+        return None;
+    }
+    let expression = Parser { tokens: &tokens, pos: 0 }.parse()?;
+    if expression.is_empty() {
+        return None;
+    }
+    resolve(&expression, reference, tz)
+}
+
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for word in input.to_lowercase().split(|c: char| c.is_whitespace() || c == ',' || c == '-') {
+        // "9am", "20min" and "1h30m" are split into numbers and words
+        let mut current = String::new();
+        let mut current_is_number = false;
+        for c in word.chars() {
+            let is_number = c.is_ascii_digit() || (current_is_number && (c == ':' || c == '.'));
+            if c == '.' && !current_is_number {
+                // "p.m." and a trailing full stop
+                continue;
+            }
+            if !current.is_empty() && is_number != current_is_number {
+                tokens.push(std::mem::take(&mut current));
+            }
+            current_is_number = is_number;
+            current.push(c);
+        }
+        if !current.is_empty() {
+            tokens.push(current.trim_end_matches(['.', ':']).to_string());
+        }
+    }
+    tokens.retain(|token| !token.is_empty());
+    tokens
+}
+
+fn number_word(word: &str) -> Option<i64> {
+    let value = match word {
+        "a" | "an" | "one" => 1,
+        "couple" | "two" => 2,
+        "three" => 3,
+        "four" => 4,
+        "five" => 5,
+        "six" => 6,
+        "seven" => 7,
+        "eight" => 8,
+        "nine" => 9,
+        "ten" => 10,
+        "eleven" => 11,
+        "twelve" => 12,
+        "fifteen" => 15,
+        "twenty" => 20,
+        "thirty" => 30,
+        "forty" => 40,
+        "fifty" => 50,
+        "sixty" => 60,
+        "ninety" => 90,
+        _ => return word.parse().ok(),
+    };
+    Some(value)
+}
+
+fn unit(word: &str) -> Option<Duration> {
+    match word {
+        "s" | "sec" | "secs" | "second" | "seconds" => Some(Duration::seconds(1)),
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(Duration::minutes(1)),
+        "h" | "hr" | "hrs" | "hour" | "hours" => Some(Duration::hours(1)),
+        "d" | "day" | "days" => Some(Duration::days(1)),
+        "w" | "week" | "weeks" => Some(Duration::weeks(1)),
+        _ => None,
+    }
+}
+
+fn weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "monday" | "mon" => Some(Weekday::Mon),
+        "tuesday" | "tue" | "tues" => Some(Weekday::Tue),
+        "wednesday" | "wed" => Some(Weekday::Wed),
+        "thursday" | "thu" | "thurs" => Some(Weekday::Thu),
+        "friday" | "fri" => Some(Weekday::Fri),
+        "saturday" | "sat" => Some(Weekday::Sat),
+        "sunday" | "sun" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn day_part(word: &str) -> Option<DayPart> {
+    match word {
+        "morning" => Some(DayPart::Morning),
+        "noon" | "midday" | "lunch" | "lunchtime" => Some(DayPart::Noon),
+        "afternoon" => Some(DayPart::Afternoon),
+        "evening" => Some(DayPart::Evening),
+        "night" => Some(DayPart::Night),
+        "midnight" => Some(DayPart::Midnight),
+        _ => None,
+    }
+}
+
+/// "9", "9:30", "21.15" or "nine"
+fn clock_number(token: &str) -> Option<(u32, Option<u32>)> {
+    if let Some((hour, minute)) = token.split_once([':', '.']) {
+        let minute: u32 = minute.parse().ok()?;
+        return (minute < 60).then_some((hour.parse().ok()?, Some(minute)));
+    }
+    if matches!(token, "a" | "an" | "couple") {
+        return None;
+    }
+    let hour = number_word(token)?;
+    (0..=24).contains(&hour).then_some((hour as u32, None))
+}
+
+struct Parser<'a> {
+    tokens: &'a [String],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn peek_at(&self, ahead: usize) -> Option<&'a str> {
+        self.tokens.get(self.pos + ahead).map(String::as_str)
+    }
+
+    fn eat(&mut self, word: &str) -> bool {
+        if self.peek() == Some(word) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse(mut self) -> Option<Expression> {
+        let mut expression = Expression::default();
+        while let Some(token) = self.peek() {
+            if token == "in" && self.peek_at(1).is_some_and(|next| next != "the") {
+                self.pos += 1;
+                let offset = self.duration()?;
+                set_once(&mut expression.offset, offset)?;
+            } else if token == "in" || FILLERS.contains(&token) {
+                self.pos += 1;
+            } else if let Some(offset) = self.duration_from_now() {
+                set_once(&mut expression.offset, offset)?;
+            } else if let Some(day) = self.day() {
+                set_once(&mut expression.day, day)?;
+            } else if token == "tonight" {
+                self.pos += 1;
+                set_once(&mut expression.day, Day::Today)?;
+                set_once(&mut expression.part, DayPart::Night)?;
+            } else if let Some(part) = day_part(token) {
+                self.pos += 1;
+                set_once(&mut expression.part, part)?;
+            } else if let Some(time) = self.clock_time() {
+                set_once(&mut expression.time, time)?;
+            } else {
+                return None;
+            }
+        }
+        Some(expression)
+    }
+
+    /// "20 minutes", "an hour and a half", "2 hours and 30 minutes", "1h 30m"
+    fn duration(&mut self) -> Option<Duration> {
+        let mut total = self.duration_term()?;
+        loop {
+            let start = self.pos;
+            self.eat("and");
+            match self.duration_term() {
+                Some(more) => total = total.checked_add(&more)?,
+                None => {
+                    self.pos = start;
+                    break;
+                }
+            }
+        }
+        Some(total)
+    }
+
+    fn duration_term(&mut self) -> Option<Duration> {
+        let start = self.pos;
+        let term = self.try_duration_term();
+        if term.is_none() {
+            self.pos = start;
+        }
+        term
+    }
+
+    fn try_duration_term(&mut self) -> Option<Duration> {
+        // "half an hour"
+        if self.eat("half") {
+            self.eat("an");
+            self.eat("a");
+            let unit = unit(self.peek()?)?;
+            self.pos += 1;
+            return Some(unit / 2);
+        }
+        // "a and a half" would be odd, but "a couple of" and "a few" are not
+        if self.peek() == Some("a") && self.peek_at(1) == Some("couple") {
+            self.pos += 1;
+        }
+        let count = number_word(self.peek()?)?;
+        self.pos += 1;
+        self.eat("of");
+        let half_before_unit = self.and_a_half();
+        let unit = unit(self.peek()?)?;
+        self.pos += 1;
+        let half_after_unit = !half_before_unit && self.and_a_half();
+        let half = if half_before_unit || half_after_unit { unit / 2 } else { Duration::zero() };
+        unit.checked_mul(i32::try_from(count).ok()?)?.checked_add(&half)
+    }
+
+    fn and_a_half(&mut self) -> bool {
+        let is_half = self.peek() == Some("and") && self.peek_at(1) == Some("a") && self.peek_at(2) == Some("half");
+        if is_half {
+            self.pos += 3;
+        }
+        is_half
+    }
+
+    /// "20 minutes from now", "two hours later"
+    fn duration_from_now(&mut self) -> Option<Duration> {
+        let start = self.pos;
+        if let Some(offset) = self.duration() {
+            if self.eat("later") || (self.eat("from") && self.eat("now")) {
+                return Some(offset);
+            }
+        }
+        self.pos = start;
+        None
+    }
+
+    fn day(&mut self) -> Option<Day> {
+        let token = self.peek()?;
+        let (day, length) = match token {
+            "today" => (Day::Today, 1),
+            "tomorrow" | "tmrw" => (Day::Tomorrow, 1),
+            "day" if self.peek_at(1) == Some("after") && self.peek_at(2) == Some("tomorrow") => {
+                (Day::Overmorrow, 3)
+            }
+            "next" if self.peek_at(1) == Some("week") => (Day::NextWeek, 2),
+            "next" => (Day::Weekday { day: weekday(self.peek_at(1)?)?, next: true }, 2),
+            _ => (Day::Weekday { day: weekday(token)?, next: false }, 1),
+        };
+        self.pos += length;
+        Some(day)
+    }
+
+    /// "9", "9:30pm", "nine thirty", "half past nine", "quarter to 5"
+    fn clock_time(&mut self) -> Option<ClockTime> {
+        let start = self.pos;
+        let time = self.try_clock_time();
+        if time.is_none() {
+            self.pos = start;
+        }
+        time
+    }
+
+    fn try_clock_time(&mut self) -> Option<ClockTime> {
+        let mut to_hour = false;
+        let (hour, minute) = match self.peek()? {
+            "half" | "quarter" => {
+                let past_minutes = if self.peek()? == "half" { 30 } else { 15 };
+                self.pos += 1;
+                let before = match self.peek()? {
+                    "past" | "after" => false,
+                    "to" | "before" if past_minutes == 15 => true,
+                    _ => return None,
+                };
+                self.pos += 1;
+                let (hour, None) = clock_number(self.peek()?)? else {
+                    return None;
+                };
+                self.pos += 1;
+                // the hour is taken back once am or pm has been applied to it
+                to_hour = before;
+                (hour, if before { 45 } else { past_minutes })
+            }
+            token => {
+                let (hour, minute) = clock_number(token)?;
+                self.pos += 1;
+                let minute = match minute {
+                    Some(minute) => minute,
+                    // "nine thirty", "9 15"
+                    None => match self.peek().and_then(number_word) {
+                        Some(minute @ 0..=59) if self.peek_at(1).and_then(unit).is_none() => {
+                            self.pos += 1;
+                            minute as u32
+                        }
+                        _ => 0,
+                    },
+                };
+                (hour, minute)
+            }
+        };
+        // a bare number followed by a unit is a duration missing its "in"
+        if self.peek().and_then(unit).is_some() {
+            return None;
+        }
+        let meridiem = match self.peek() {
+            Some("am") => Some(Meridiem::Am),
+            Some("pm") => Some(Meridiem::Pm),
+            _ => None,
+        };
+        if meridiem.is_some() {
+            self.pos += 1;
+            if hour == 0 || hour > 12 {
+                return None;
+            }
+        }
+        if hour > 23 && !(hour == 24 && minute == 0) {
+            return None;
+        }
+        Some(ClockTime { hour: hour % 24, minute, meridiem, to_hour })
+    }
+}
+
+/// Each part of an expression may only be given once, "tomorrow on Friday" is nonsense.
+fn set_once<T>(slot: &mut Option<T>, value: T) -> Option<()> {
+    if slot.is_some() {
+        return None;
+    }
+    *slot = Some(value);
+    Some(())
+}
+
+fn resolve(expression: &Expression, reference: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+    let now = reference.with_timezone(&tz);
+    let today = now.date_naive();
+
+    let offset_days = match expression.offset {
+        // "in 3 days at 10" picks the day, "in 20 minutes" is exact
+        Some(offset) if expression.day.is_none() && expression.time.is_none() && expression.part.is_none() => {
+            return reference.checked_add_signed(offset);
+        }
+        Some(offset) if offset.num_seconds() % 86_400 == 0 && expression.day.is_none() => {
+            Some(offset.num_days())
+        }
+        Some(_) => return None,
+        None => None,
+    };
+    let day = offset_days.map(Day::InDays).or(expression.day);
+
+    let (hour, minute, ambiguous) = time_of_day(expression)?;
+    let time = NaiveTime::from_hms_opt(hour, minute, 0)?;
+    let at = |date: NaiveDate| {
+        // midnight belongs to the end of the day it is said with
+        let date = if expression.part == Some(DayPart::Midnight) { date.succ_opt()? } else { date };
+        Some(localize(tz, date.and_time(time)))
+    };
+    // "at 3" said after 3am means 3pm when that is still ahead
+    let soonest = |date: NaiveDate| -> Option<DateTime<Utc>> {
+        let candidate = at(date)?;
+        if candidate <= reference && ambiguous && candidate + Duration::hours(12) > reference {
+            return Some(candidate + Duration::hours(12));
+        }
+        Some(candidate)
+    };
+
+    match day {
+        None => {
+            let candidate = soonest(today)?;
+            if candidate > reference {
+                Some(candidate)
+            } else {
+                at(today.succ_opt()?)
+            }
+        }
+        Some(Day::Today) => soonest(today),
+        Some(Day::Tomorrow) => at(days_after(today, 1)?),
+        Some(Day::Overmorrow) => at(days_after(today, 2)?),
+        Some(Day::NextWeek) => at(days_after(today, 7)?),
+        Some(Day::InDays(days)) => at(days_after(today, days)?),
+        Some(Day::Weekday { day, next }) => {
+            let ahead = (i64::from(day.num_days_from_monday()) - i64::from(today.weekday().num_days_from_monday()))
+                .rem_euclid(7);
+            let candidate = at(days_after(today, ahead)?)?;
+            if ahead == 0 && (next || candidate <= reference) {
+                at(days_after(today, 7)?)
+            } else {
+                Some(candidate)
+            }
+        }
+    }
+}
+
+fn days_after(date: NaiveDate, days: i64) -> Option<NaiveDate> {
+    date.checked_add_signed(Duration::try_days(days)?)
+}
+
+/// The hour and minute asked for, and whether an hour like "3" could still mean 3pm.
+fn time_of_day(expression: &Expression) -> Option<(u32, u32, bool)> {
+    let Some(time) = expression.time else {
+        let (hour, minute) = expression.part.unwrap_or(DayPart::Morning).default_time();
+        return Some((hour, minute, false));
+    };
+
+    if expression.part == Some(DayPart::Midnight) {
+        // "12 midnight"
+        return (time.hour % 12 == 0 && time.meridiem.is_none() && !time.to_hour).then_some((0, time.minute, false));
+    }
+    let hour = match (time.meridiem, expression.part) {
+        (Some(Meridiem::Am), _) => time.hour % 12,
+        (Some(Meridiem::Pm), _) => time.hour % 12 + 12,
+        (None, Some(part)) if part.is_after_noon() && time.hour < 12 => time.hour + 12,
+        (None, Some(DayPart::Morning)) if time.hour >= 12 => return None,
+        _ => time.hour,
+    };
+    let (hour, spoken) = if time.to_hour {
+        // "quarter to 12" is 11:45, which could be either half of the day too
+        ((hour + 23) % 24, (time.hour + 11) % 12)
+    } else {
+        (hour, time.hour)
+    };
+    let ambiguous = time.meridiem.is_none() && expression.part.is_none() && (1..12).contains(&spoken);
+    Some((hour, time.minute, ambiguous))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use chrono_tz::Europe::Helsinki;
+
+    /// Friday 2025-01-10 at noon in Helsinki
+    fn reference() -> DateTime<Utc> {
+        local(2025, 1, 10, 12, 0)
+    }
+
+    fn local(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Helsinki.with_ymd_and_hms(y, m, d, h, min, 0).unwrap().with_timezone(&Utc)
+    }
+
+    fn parse_at(input: &str) -> Option<DateTime<Utc>> {
+        parse(input, reference(), Helsinki)
+    }
+
+    #[track_caller]
+    fn assert_in(input: &str, offset: Duration) {
+        assert_eq!(parse_at(input), Some(reference() + offset), "{:?}", input);
+    }
+
+    #[track_caller]
+    fn assert_local(input: &str, expected: DateTime<Utc>) {
+        assert_eq!(parse_at(input), Some(expected), "{:?}", input);
+    }
+
+    #[test]
+    fn relative_durations() {
+        assert_in("in 20 minutes", Duration::minutes(20));
+        assert_in("In 20 mins.", Duration::minutes(20));
+        assert_in("in an hour", Duration::hours(1));
+        assert_in("in half an hour", Duration::minutes(30));
+        assert_in("in an hour and a half", Duration::minutes(90));
+        assert_in("in one and a half hours", Duration::minutes(90));
+        assert_in("in 2 hours and 30 minutes", Duration::minutes(150));
+        assert_in("in 1h30m", Duration::minutes(90));
+        assert_in("in 1h 30m", Duration::minutes(90));
+        assert_in("in a couple of hours", Duration::hours(2));
+        assert_in("in ten seconds", Duration::seconds(10));
+        assert_in("in 3 days", Duration::days(3));
+        assert_in("in two weeks", Duration::weeks(2));
+    }
+
+    #[test]
+    fn durations_from_now() {
+        assert_in("20 minutes from now", Duration::minutes(20));
+        assert_in("five minutes later", Duration::minutes(5));
+        assert_in("an hour from now", Duration::hours(1));
+    }
+
+    #[test]
+    fn tomorrow_and_friends() {
+        assert_local("tomorrow", local(2025, 1, 11, 9, 0));
+        assert_local("tomorrow at 9", local(2025, 1, 11, 9, 0));
+        assert_local("at 9 tomorrow", local(2025, 1, 11, 9, 0));
+        assert_local("tomorrow at 9pm", local(2025, 1, 11, 21, 0));
+        assert_local("tomorrow at 9 p.m.", local(2025, 1, 11, 21, 0));
+        assert_local("tomorrow morning", local(2025, 1, 11, 9, 0));
+        assert_local("tomorrow morning at 7:30", local(2025, 1, 11, 7, 30));
+        assert_local("tomorrow evening at 7", local(2025, 1, 11, 19, 0));
+        assert_local("tomorrow at noon", local(2025, 1, 11, 12, 0));
+        assert_local("the day after tomorrow at 8 am", local(2025, 1, 12, 8, 0));
+        assert_local("day after tomorrow", local(2025, 1, 12, 9, 0));
+    }
+
+    #[test]
+    fn today_and_tonight() {
+        assert_local("tonight", local(2025, 1, 10, 21, 0));
+        assert_local("tonight at 10", local(2025, 1, 10, 22, 0));
+        assert_local("this evening", local(2025, 1, 10, 18, 0));
+        assert_local("in the afternoon", local(2025, 1, 10, 15, 0));
+        assert_local("today at 5pm", local(2025, 1, 10, 17, 0));
+        assert_local("today at 3", local(2025, 1, 10, 15, 0));
+        assert_local("at midnight", local(2025, 1, 11, 0, 0));
+        assert_local("at 12 midnight", local(2025, 1, 11, 0, 0));
+    }
+
+    #[test]
+    fn bare_times_pick_the_next_occurrence() {
+        assert_local("at 3", local(2025, 1, 10, 15, 0));
+        assert_local("at 11", local(2025, 1, 10, 23, 0));
+        assert_local("at 9:30", local(2025, 1, 10, 21, 30));
+        assert_local("at 21:00", local(2025, 1, 10, 21, 0));
+        assert_local("14.30", local(2025, 1, 10, 14, 30));
+        assert_local("at 8am", local(2025, 1, 11, 8, 0));
+        assert_local("noon", local(2025, 1, 11, 12, 0));
+        assert_local("at 13 o'clock", local(2025, 1, 10, 13, 0));
+        assert_local("at nine thirty in the evening", local(2025, 1, 10, 21, 30));
+    }
+
+    #[test]
+    fn spoken_clock_phrases() {
+        assert_local("half past nine tomorrow", local(2025, 1, 11, 9, 30));
+        assert_local("quarter past 4 in the afternoon", local(2025, 1, 10, 16, 15));
+        assert_local("quarter to 5 in the afternoon", local(2025, 1, 10, 16, 45));
+        assert_local("tomorrow at quarter to 8 am", local(2025, 1, 11, 7, 45));
+        assert_local("tomorrow at quarter to 12 pm", local(2025, 1, 11, 11, 45));
+        assert_local("quarter to 1 pm", local(2025, 1, 10, 12, 45));
+        assert_local("tomorrow at quarter to 1 am", local(2025, 1, 11, 0, 45));
+        assert_local("quarter to 12", local(2025, 1, 10, 23, 45));
+    }
+
+    #[test]
+    fn weekdays() {
+        // the reference is a Friday
+        assert_local("next friday morning", local(2025, 1, 17, 9, 0));
+        assert_local("friday at 3pm", local(2025, 1, 10, 15, 0));
+        assert_local("on friday at 9am", local(2025, 1, 17, 9, 0));
+        assert_local("on monday", local(2025, 1, 13, 9, 0));
+        assert_local("next Monday at 10", local(2025, 1, 13, 10, 0));
+        assert_local("this sunday evening", local(2025, 1, 12, 18, 0));
+        assert_local("Wed, 2 pm", local(2025, 1, 15, 14, 0));
+        assert_local("next week", local(2025, 1, 17, 9, 0));
+    }
+
+    #[test]
+    fn day_offsets_with_a_time() {
+        assert_local("in 3 days at 10", local(2025, 1, 13, 10, 0));
+        assert_local("in a week in the morning", local(2025, 1, 17, 9, 0));
+    }
+
+    #[test]
+    fn rejects_what_it_does_not_understand() {
+        for input in [
+            "",
+            "buy milk",
+            "in",
+            "after the meeting",
+            "tomorrow at 25",
+            "at 13pm",
+            "tomorrow on friday",
+            "in 20 minutes tomorrow",
+            "at 9:75",
+            "in the morning at 14",
+            "at the",
+            "at 3 midnight",
+            // further than any calendar goes
+            "in 2000000000 weeks",
+            "in 2000000000 days at 9",
+            "in 2000000000 hours and 2000000000 hours",
+            "in 1000000 weeks and 2000000000 weeks",
+        ] {
+            assert_eq!(parse_at(input), None, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn respects_dst_transitions() {
+        // clocks go forward at 03:00 on 2025-03-30 in Helsinki
+        let saturday = local(2025, 3, 29, 12, 0);
+        assert_eq!(parse("in 24 hours", saturday, Helsinki), Some(saturday + Duration::hours(24)));
+        assert_eq!(parse("tomorrow at noon", saturday, Helsinki), Some(local(2025, 3, 30, 12, 0)));
+        assert_eq!(
+            parse("tomorrow at 3:30am", saturday, Helsinki),
+            Some(Utc.with_ymd_and_hms(2025, 3, 30, 1, 30, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn uses_the_callers_timezone() {
+        let new_york: Tz = "America/New_York".parse().unwrap();
+        assert_eq!(
+            parse("tomorrow at 9", reference(), new_york),
+            Some(Utc.with_ymd_and_hms(2025, 1, 11, 14, 0, 0).unwrap())
+        );
+    }
+}
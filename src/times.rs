@@ -0,0 +1,141 @@
+use chrono::{DateTime, Duration, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::fmt;
+use crate::time_expr;
+
+
+/// Date-only reminders go off at this hour, local time.
+const DATE_ONLY_HOUR: u32 = 9;
+
+/// How far in the past a time may be and still be accepted, to absorb the delay
+/// between the caller speaking and the tool call arriving.
+const PAST_GRACE: Duration = Duration::minutes(1);
+
+const NAIVE_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Why a `remind_at` value was refused. `Display` is phrased so the assistant can
+/// say it to the caller as is.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeError {
+    Unrecognized(String),
+    InPast(DateTime<Utc>),
+}
+
+impl fmt::Display for TimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeError::Unrecognized(input) => write!(
+                f,
+                "I couldn't understand the time \"{}\". Could you tell me the date and time again?",
+                input
+            ),
+            TimeError::InPast(_) => write!(
+                f,
+                "That time has already passed. When should I remind you instead?"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TimeError {}
+
+/// Parses an RFC 3339 timestamp, a local date and time without offset, a plain date,
+/// or a spoken expression like "tomorrow at 9" relative to `now`, the moment the
+/// caller said it. Anything earlier than `now` is refused.
+pub fn parse_remind_at(input: &str, tz: Tz, now: DateTime<Utc>) -> Result<DateTime<Utc>, TimeError> {
+    let at = parse_absolute(input, tz)
+        .or_else(|| time_expr::parse(input, now, tz))
+        .ok_or_else(|| TimeError::Unrecognized(input.to_string()))?;
+    if at < now - PAST_GRACE {
+        return Err(TimeError::InPast(at));
+    }
+    Ok(at)
+}
+
+fn parse_absolute(input: &str, tz: Tz) -> Option<DateTime<Utc>> {
+    let input = input.trim();
+    if let Ok(at) = DateTime::parse_from_rfc3339(input) {
+        return Some(at.with_timezone(&Utc));
+    }
+    if let Some(naive) = NAIVE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+    {
+        return Some(localize(tz, naive));
+    }
+    let date = NaiveDate::parse_from_str(input, "%Y-%m-%d").ok()?;
+    Some(localize(tz, date.and_time(NaiveTime::from_hms_opt(DATE_ONLY_HOUR, 0, 0)?)))
+}
+
+/// Resolves a wall-clock time in `tz`. Times skipped by a spring-forward transition
+/// move forward by the skipped hour; times repeated when clocks fall back take the
+/// earlier of the two.
+pub fn localize(tz: Tz, naive: NaiveDateTime) -> DateTime<Utc> {
+    match tz.from_local_datetime(&naive) {
+        LocalResult::Single(at) => at.with_timezone(&Utc),
+        LocalResult::Ambiguous(earliest, _) => earliest.with_timezone(&Utc),
+        LocalResult::None => localize(tz, naive + Duration::hours(1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::Europe::Helsinki;
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    fn parse(input: &str) -> Result<DateTime<Utc>, TimeError> {
+        parse_remind_at(input, Helsinki, utc(2025, 1, 10, 12, 0))
+    }
+
+    #[test]
+    fn accepts_rfc3339_with_any_offset() {
+        assert_eq!(parse("2025-01-11T09:00:00+02:00"), Ok(utc(2025, 1, 11, 7, 0)));
+        assert_eq!(parse("2025-01-11T09:00:00Z"), Ok(utc(2025, 1, 11, 9, 0)));
+        assert_eq!(parse("2025-01-11 09:00:00.5-05:00"), Ok(utc(2025, 1, 11, 14, 0) + Duration::milliseconds(500)));
+    }
+
+    #[test]
+    fn naive_times_are_local() {
+        assert_eq!(parse("2025-01-11T09:00:00"), Ok(utc(2025, 1, 11, 7, 0)));
+        assert_eq!(parse("2025-07-11 09:00"), Ok(utc(2025, 7, 11, 6, 0)));
+    }
+
+    #[test]
+    fn date_only_means_morning() {
+        assert_eq!(parse("2025-01-11"), Ok(utc(2025, 1, 11, 7, 0)));
+    }
+
+    #[test]
+    fn falls_back_to_spoken_expressions() {
+        assert_eq!(parse("in 20 minutes"), Ok(utc(2025, 1, 10, 12, 20)));
+        assert_eq!(parse("tomorrow at 9"), Ok(utc(2025, 1, 11, 7, 0)));
+        assert_eq!(parse("today at 8am"), Err(TimeError::InPast(utc(2025, 1, 10, 6, 0))));
+    }
+
+    #[test]
+    fn rejects_garbage_and_the_past() {
+        assert_eq!(parse("next tuesday-ish"), Err(TimeError::Unrecognized("next tuesday-ish".to_string())));
+        assert_eq!(parse("2025-01-10T13:00:00"), Err(TimeError::InPast(utc(2025, 1, 10, 11, 0))));
+        // a few seconds late is still fine
+        assert!(parse("2025-01-10T11:59:30Z").is_ok());
+    }
+
+    #[test]
+    fn localize_handles_dst_transitions() {
+        // 2025-03-30 03:30 does not exist in Helsinki, clocks jump from 03:00 to 04:00
+        let gap = NaiveDate::from_ymd_opt(2025, 3, 30).unwrap().and_hms_opt(3, 30, 0).unwrap();
+        assert_eq!(localize(Helsinki, gap), utc(2025, 3, 30, 1, 30));
+        // 2025-10-26 03:30 happens twice, the first one is still summer time
+        let repeated = NaiveDate::from_ymd_opt(2025, 10, 26).unwrap().and_hms_opt(3, 30, 0).unwrap();
+        assert_eq!(localize(Helsinki, repeated), utc(2025, 10, 26, 0, 30));
+    }
+}
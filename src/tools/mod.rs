@@ -0,0 +1,656 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use diesel::SqliteConnection;
+use schemars::gen::SchemaSettings;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tracing::Instrument;
+use crate::answers::AnswerProviders;
+use crate::config::Config;
+use crate::db::{self, Pool};
+use crate::error::Error;
+use crate::models::{ToolCallResponse, User};
+use crate::sms::SmsSender;
+
+pub mod questions;
+pub mod reminders;
+pub mod timezone;
+
+
+/// A function the voice assistant can call. Each one is registered under its
+/// `name` and describes its arguments with a JSON schema.
+#[async_trait]
+pub trait Tool: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// Older names the assistant may still call the tool by.
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+    fn description(&self) -> &'static str;
+    /// JSON schema of the `arguments` object.
+    fn parameters(&self) -> Value;
+    /// Whether the tool only looks things up. In a batch, look-ups run alongside
+    /// each other and the changes after them, while calls that change something
+    /// take turns in the order they were made.
+    fn read_only(&self) -> bool {
+        false
+    }
+    async fn execute(&self, ctx: &ToolContext, arguments: Value) -> Result<ToolCallResponse, Error>;
+}
+
+/// Everything a tool call can use: shared services plus who is calling and when.
+#[derive(Clone)]
+pub struct ToolContext {
+    pub pool: Pool,
+    pub sms: Arc<dyn SmsSender>,
+    pub answers: AnswerProviders,
+    /// Zone for callers who haven't set their own.
+    pub default_timezone: Tz,
+    /// The caller, absent for web calls.
+    pub user: Option<User>,
+    /// When the caller spoke, which relative times count from.
+    pub sent_at: DateTime<Utc>,
+}
+
+impl ToolContext {
+    /// Runs `query` on a pooled connection without blocking the runtime.
+    pub async fn db<T, F>(&self, query: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut SqliteConnection) -> Result<T, diesel::result::Error> + Send + 'static,
+        T: Send + 'static,
+    {
+        db::run(&self.pool, query).await
+    }
+
+    /// The zone the caller speaks times in.
+    pub fn timezone(&self) -> Tz {
+        self.user
+            .as_ref()
+            .map_or(self.default_timezone, |user| user.timezone(self.default_timezone))
+    }
+}
+
+/// Arguments that take no fields, and refuse any that are given.
+#[derive(Deserialize, JsonSchema, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct NoArguments {}
+
+/// Decodes the arguments into the tool's own type. Arguments sent as a JSON string
+/// are unwrapped first, and missing ones count as `{}`.
+pub fn parse_arguments<T: DeserializeOwned>(arguments: Value) -> Result<T, Error> {
+    let arguments = match arguments {
+        Value::Null => Value::Object(Default::default()),
+        Value::String(text) => serde_json::from_str(&text).map_err(|_| {
+            Error::InvalidArguments("The arguments are not a valid JSON object.".to_string())
+        })?,
+        arguments => arguments,
+    };
+    serde_path_to_error::deserialize(arguments)
+        .map_err(|e| Error::InvalidArguments(describe_decode_error(&e)))
+}
+
+/// Rephrases serde's complaint about the arguments as a sentence naming the field.
+fn describe_decode_error(e: &serde_path_to_error::Error<serde_json::Error>) -> String {
+    let path = e.path().to_string();
+    let within = |field: &str| {
+        if path == "." {
+            field.to_string()
+        } else {
+            format!("{}.{}", path, field)
+        }
+    };
+    let message = e.inner().to_string();
+    // serde_json appends the position, which means nothing for a decoded value
+    let message = message.split(" at line ").next().unwrap_or(&message).replace('`', "");
+
+    if let Some(field) = message.strip_prefix("missing field ") {
+        format!("The {} argument is missing.", within(field))
+    } else if let Some(rest) = message.strip_prefix("unknown field ") {
+        // the path already ends in the unknown field
+        let (_, expected) = rest.split_once(", expected ").unwrap_or((rest, ""));
+        let expected = expected.trim_start_matches("one of ");
+        if expected.is_empty() || expected == "fields" {
+            format!("There is no {} argument, this tool takes none.", path)
+        } else {
+            format!("There is no {} argument, only {}.", path, expected)
+        }
+    } else if let Some(rest) = message.strip_prefix("unknown variant ") {
+        let (value, expected) = rest.split_once(", expected ").unwrap_or((rest, ""));
+        let expected = expected.trim_start_matches("one of ");
+        format!("The {} argument can't be {}, it has to be one of {}.", path, value, expected)
+    } else if let Some(rest) = message.strip_prefix("invalid type: ") {
+        let (found, expected) = rest.split_once(", expected ").unwrap_or((rest, "something else"));
+        let expected = match expected {
+            "i64" | "u32" | "u64" | "i32" => "a whole number",
+            expected => expected,
+        };
+        format!("The {} argument should be {}, not {}.", path, expected, found)
+    } else {
+        format!("The {} argument is not valid: {}.", path, message)
+    }
+}
+
+/// JSON schema for an argument type, inlined and without the `null`s serde accepts
+/// for missing options, the way function-calling LLMs expect it. Field doc comments
+/// become the parameter descriptions.
+pub fn schema_of<T: JsonSchema>() -> Value {
+    let generator = SchemaSettings::draft07()
+        .with(|settings| {
+            settings.option_add_null_type = false;
+            settings.inline_subschemas = true;
+            settings.meta_schema = None;
+        })
+        .into_generator();
+    let mut schema = serde_json::to_value(generator.into_root_schema_for::<T>()).unwrap_or_default();
+    if let Some(schema) = schema.as_object_mut() {
+        // the type's name and doc comment are for us, the tool has its own description
+        schema.remove("title");
+        schema.remove("description");
+        schema.entry("properties").or_insert_with(|| Value::Object(Default::default()));
+    }
+    schema
+}
+
+/// A tool as the voice platform's assistant configuration describes it.
+#[derive(Serialize, Debug)]
+pub struct ToolDefinition {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub function: FunctionDefinition,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<ServerDefinition>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FunctionDefinition {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub parameters: Value,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ServerDefinition {
+    pub url: String,
+}
+
+/// Reminders belong to a phone number, so web calls can't have any.
+pub fn unknown_caller() -> ToolCallResponse {
+    ToolCallResponse::Message(
+        "I can only keep reminders for you when you call from your phone.".to_string(),
+    )
+}
+
+/// The tools the assistant can call, looked up by the name in each tool call.
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        ToolRegistry::default()
+    }
+
+    /// Every tool this server knows.
+    pub fn builtin() -> Self {
+        ToolRegistry::new()
+            .register(reminders::StoreUserReminder)
+            .register(reminders::GetUserReminders)
+            .register(reminders::DeleteAllReminders)
+            .register(reminders::DeleteReminder)
+            .register(reminders::UpdateReminder)
+            .register(reminders::SnoozeReminder)
+            .register(timezone::SetUserTimezone)
+            .register(questions::AskQuestion)
+    }
+
+    /// The built-in tools, narrowed down by `tools.enabled` and `tools.disabled`.
+    pub fn from_config(config: &Config) -> Self {
+        let mut registry = ToolRegistry::builtin();
+        if let Some(enabled) = &config.tools.enabled {
+            registry = registry.only(enabled);
+        }
+        registry.without(&config.tools.disabled)
+    }
+
+    pub fn register(mut self, tool: impl Tool + 'static) -> Self {
+        self.tools.push(Arc::new(tool));
+        self
+    }
+
+    /// Keeps only the named tools.
+    pub fn only(mut self, names: &[String]) -> Self {
+        self.tools.retain(|tool| names.iter().any(|name| name == tool.name()));
+        self
+    }
+
+    /// Drops the named tools.
+    pub fn without(mut self, names: &[String]) -> Self {
+        self.tools.retain(|tool| !names.iter().any(|name| name == tool.name()));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools
+            .iter()
+            .find(|tool| tool.name() == name || tool.aliases().contains(&name))
+            .cloned()
+    }
+
+    pub fn tools(&self) -> &[Arc<dyn Tool>] {
+        &self.tools
+    }
+
+    /// Definitions of every registered tool, pointing at the tool-call webhook of
+    /// the server at `public_url` when it is known.
+    pub fn definitions(&self, public_url: Option<&str>) -> Vec<ToolDefinition> {
+        self.tools
+            .iter()
+            .map(|tool| ToolDefinition {
+                kind: "function",
+                function: FunctionDefinition {
+                    name: tool.name(),
+                    description: tool.description(),
+                    parameters: tool.parameters(),
+                },
+                server: public_url.map(|url| ServerDefinition {
+                    url: format!("{}/tool-call", url.trim_end_matches('/')),
+                }),
+            })
+            .collect()
+    }
+
+    /// Runs the named tool. Unknown or disabled tools and bad arguments are answered
+    /// with a message for the assistant rather than failing the request.
+    pub async fn call(&self, name: &str, ctx: &ToolContext, arguments: Value) -> Result<ToolCallResponse, Error> {
+        let Some(tool) = self.get(name) else {
+            tracing::warn!("Unknown function call: {:#?}", name);
+            return Ok(ToolCallResponse::Message("Unknown function call".to_string()));
+        };
+        match tool.execute(ctx, arguments).await {
+            Err(Error::InvalidArguments(reason)) => {
+                tracing::warn!("Bad arguments for {}: {}", name, reason);
+                Ok(ToolCallResponse::Message(format!("{} Please call {} again with that fixed.", reason, tool.name())))
+            }
+            result => result,
+        }
+    }
+
+    /// Runs a batch of `(name, arguments)` calls side by side, except that every
+    /// call waits for the last call to a tool that isn't [`Tool::read_only`] before
+    /// it, so "delete everything, then remember this" keeps its order and "remember
+    /// this, then list them" sees the new reminder. Calls still running at the
+    /// deadline fail with [`Error::TimedOut`] and are abandoned, except changes that
+    /// had already started: a write can't be stopped halfway, so they are left to
+    /// finish and fail with [`Error::Unfinished`] instead. Results come back in the
+    /// order of the calls.
+    pub async fn call_batch(
+        &self,
+        ctx: &ToolContext,
+        calls: Vec<(String, Value)>,
+        deadline: Duration,
+    ) -> Vec<Result<ToolCallResponse, Error>> {
+        let give_up_at = Instant::now() + deadline;
+        let mut last_change: Option<watch::Receiver<()>> = None;
+        let tasks: Vec<_> = calls
+            .into_iter()
+            .map(|(name, arguments)| {
+                let registry = self.clone();
+                let ctx = ctx.clone();
+                // a change holds `done` until it finishes, aborts or panics, and the
+                // calls after it wait for it to be dropped
+                let (previous, done) = match self.get(&name) {
+                    Some(tool) if tool.read_only() => (last_change.clone(), None),
+                    _ => {
+                        let (done, finished) = watch::channel(());
+                        (last_change.replace(finished), Some(done))
+                    }
+                };
+                let started = done.as_ref().map(|_| Arc::new(AtomicBool::new(false)));
+                let starting = started.clone();
+                let call = async move {
+                    if let Some(mut previous) = previous {
+                        let _ = previous.changed().await;
+                    }
+                    if let Some(starting) = starting {
+                        starting.store(true, Ordering::SeqCst);
+                    }
+                    let _done = done;
+                    registry.call(&name, &ctx, arguments).await
+                };
+                // stays in the request's span, so its logs keep the request and tool call IDs
+                (tokio::spawn(call.in_current_span()), started)
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(tasks.len());
+        for (mut task, started) in tasks {
+            let result = match tokio::time::timeout_at(give_up_at, &mut task).await {
+                Ok(Ok(result)) => result,
+                Ok(Err(e)) => Err(Error::Crashed(e.to_string())),
+                Err(_) if started.is_some_and(|started| started.load(Ordering::SeqCst)) => {
+                    // dropping the handle leaves the change running to the end
+                    Err(Error::Unfinished(deadline))
+                }
+                Err(_) => {
+                    task.abort();
+                    Err(Error::TimedOut(deadline))
+                }
+            };
+            results.push(result);
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::sms::LogSms;
+    use serde_json::json;
+
+    pub(crate) fn context(user: Option<User>) -> ToolContext {
+        ToolContext {
+            pool: crate::test_pool(),
+            sms: Arc::new(LogSms),
+            answers: AnswerProviders::default(),
+            default_timezone: Tz::Europe__Helsinki,
+            user,
+            sent_at: Utc::now(),
+        }
+    }
+
+    fn message(response: ToolCallResponse) -> String {
+        match response {
+            ToolCallResponse::Message(text) => text,
+            other => panic!("expected a message, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatches_by_name_and_alias() {
+        let registry = ToolRegistry::builtin();
+        let ctx = context(None);
+
+        assert_eq!(
+            message(registry.call("GetUserReminders", &ctx, json!({})).await.unwrap()),
+            message(unknown_caller())
+        );
+        assert_eq!(registry.get("AskPerplexity").unwrap().name(), "AskQuestion");
+        assert_eq!(
+            message(registry.call("LaunchRockets", &ctx, json!({})).await.unwrap()),
+            "Unknown function call"
+        );
+    }
+
+    #[tokio::test]
+    async fn disabled_tools_are_unknown() {
+        let registry = ToolRegistry::builtin().without(&["AskQuestion".to_string()]);
+        assert!(registry.get("AskQuestion").is_none());
+        assert!(registry.get("AskPerplexity").is_none());
+
+        let registry = ToolRegistry::builtin().only(&["StoreUserReminder".to_string()]);
+        let names: Vec<&str> = registry.tools().iter().map(|tool| tool.name()).collect();
+        assert_eq!(names, vec!["StoreUserReminder"]);
+    }
+
+    async fn complaint(tool: &str, arguments: Value) -> String {
+        let ctx = context(None);
+        message(ToolRegistry::builtin().call(tool, &ctx, arguments).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn bad_arguments_are_explained_to_the_assistant() {
+        assert_eq!(
+            complaint("StoreUserReminder", json!({ "message": "take pills" })).await,
+            "The remind_at argument is missing. Please call StoreUserReminder again with that fixed."
+        );
+        assert_eq!(
+            complaint("SnoozeReminder", json!({ "id": "abc", "minutes": "ten" })).await,
+            "The minutes argument should be a whole number, not string \"ten\". Please call SnoozeReminder again with that fixed."
+        );
+        assert_eq!(
+            complaint("DeleteReminder", json!({ "id": "abc", "force": true })).await,
+            "There is no force argument, only id. Please call DeleteReminder again with that fixed."
+        );
+        assert_eq!(
+            complaint("GetUserReminders", json!({ "user": "me" })).await,
+            "There is no user argument, this tool takes none. Please call GetUserReminders again with that fixed."
+        );
+    }
+
+    #[tokio::test]
+    async fn nested_arguments_name_the_full_path() {
+        let arguments = json!({
+            "message": "bins",
+            "remind_at": "tomorrow at 7",
+            "recurrence": { "frequency": "yearly" }
+        });
+        assert_eq!(
+            complaint("StoreUserReminder", arguments).await,
+            "The recurrence.frequency argument can't be yearly, it has to be one of daily, weekly, monthly. \
+             Please call StoreUserReminder again with that fixed."
+        );
+        let arguments = json!({ "message": "bins", "remind_at": "tomorrow at 7", "recurrence": {} });
+        assert!(complaint("StoreUserReminder", arguments).await.starts_with("The recurrence.frequency argument is missing."));
+    }
+
+    #[tokio::test]
+    async fn failures_stay_with_their_own_call() {
+        use crate::models::ToolCallResult;
+        use diesel::connection::SimpleConnection;
+
+        let mut ctx = context(None);
+        let user = crate::users::find_or_create_by_phone(&mut ctx.pool.get().unwrap(), "+358401234567").unwrap();
+        ctx.user = Some(user);
+        let registry = ToolRegistry::builtin();
+        ctx.pool.get().unwrap().batch_execute("DROP TABLE reminders").unwrap();
+
+        let failed = registry.call("GetUserReminders", &ctx, json!({})).await;
+        assert!(matches!(failed, Err(Error::Database(_))));
+        let timezone = registry.call("SetUserTimezone", &ctx, json!({ "timezone": "Europe/Paris" })).await;
+
+        let results = serde_json::to_value(vec![
+            ToolCallResult::new("first".to_string(), failed),
+            ToolCallResult::new("second".to_string(), timezone),
+        ])
+        .unwrap();
+        assert_eq!(results[0]["toolCallId"], "first");
+        assert!(results[0]["error"].as_str().unwrap().starts_with("database error"));
+        assert!(results[0].get("result").is_none());
+        assert_eq!(results[1]["result"], "Got it, I'll use Europe/Paris time from now on.");
+        assert!(results[1].get("error").is_none());
+    }
+
+    /// Waits the given number of milliseconds, then says how long it waited.
+    struct Sleepy;
+
+    #[async_trait]
+    impl Tool for Sleepy {
+        fn name(&self) -> &'static str {
+            "Sleepy"
+        }
+
+        fn description(&self) -> &'static str {
+            "Takes its time."
+        }
+
+        fn parameters(&self) -> Value {
+            json!({ "type": "object" })
+        }
+
+        fn read_only(&self) -> bool {
+            true
+        }
+
+        async fn execute(&self, _ctx: &ToolContext, arguments: Value) -> Result<ToolCallResponse, Error> {
+            let ms = arguments["ms"].as_u64().unwrap();
+            tokio::time::sleep(Duration::from_millis(ms)).await;
+            Ok(ToolCallResponse::Message(format!("slept {}", ms)))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn batches_run_side_by_side_in_order() {
+        let registry = ToolRegistry::new().register(Sleepy);
+        let calls = [300, 100, 200]
+            .into_iter()
+            .map(|ms| ("Sleepy".to_string(), json!({ "ms": ms })))
+            .collect();
+
+        let started = Instant::now();
+        let results = registry.call_batch(&context(None), calls, Duration::from_secs(10)).await;
+        assert_eq!(started.elapsed(), Duration::from_millis(300));
+        let said: Vec<String> = results.into_iter().map(|result| message(result.unwrap())).collect();
+        assert_eq!(said, ["slept 300", "slept 100", "slept 200"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stragglers_time_out_at_the_deadline() {
+        let registry = ToolRegistry::new().register(Sleepy);
+        let calls = vec![
+            ("Sleepy".to_string(), json!({ "ms": 100 })),
+            ("Sleepy".to_string(), json!({ "ms": 60_000 })),
+        ];
+
+        let started = Instant::now();
+        let results = registry.call_batch(&context(None), calls, Duration::from_secs(1)).await;
+        assert_eq!(started.elapsed(), Duration::from_secs(1));
+        assert!(matches!(&results[0], Ok(ToolCallResponse::Message(text)) if text == "slept 100"));
+        assert!(matches!(results[1], Err(Error::TimedOut(_))));
+    }
+
+    /// Waits the given number of milliseconds, then writes its note down.
+    #[derive(Clone, Default)]
+    struct Scribe(Arc<std::sync::Mutex<Vec<String>>>);
+
+    #[async_trait]
+    impl Tool for Scribe {
+        fn name(&self) -> &'static str {
+            "Scribe"
+        }
+
+        fn description(&self) -> &'static str {
+            "Writes things down."
+        }
+
+        fn parameters(&self) -> Value {
+            json!({ "type": "object" })
+        }
+
+        async fn execute(&self, _ctx: &ToolContext, arguments: Value) -> Result<ToolCallResponse, Error> {
+            tokio::time::sleep(Duration::from_millis(arguments["ms"].as_u64().unwrap())).await;
+            let note = arguments["note"].as_str().unwrap().to_string();
+            self.0.lock().unwrap().push(note.clone());
+            Ok(ToolCallResponse::Message(note))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn changes_take_turns_and_look_ups_wait_for_the_change_before_them() {
+        let scribe = Scribe::default();
+        let registry = ToolRegistry::new().register(Sleepy).register(scribe.clone());
+        let calls = vec![
+            ("Sleepy".to_string(), json!({ "ms": 350 })),
+            ("Scribe".to_string(), json!({ "ms": 300, "note": "first" })),
+            ("Scribe".to_string(), json!({ "ms": 100, "note": "second" })),
+            ("Sleepy".to_string(), json!({ "ms": 50 })),
+        ];
+
+        let started = Instant::now();
+        let results = registry.call_batch(&context(None), calls, Duration::from_secs(10)).await;
+        // the first look-up runs alongside the changes, the last one only after them
+        assert_eq!(started.elapsed(), Duration::from_millis(450));
+        let said: Vec<String> = results.into_iter().map(|result| message(result.unwrap())).collect();
+        assert_eq!(said, ["slept 350", "first", "second", "slept 50"]);
+        assert_eq!(*scribe.0.lock().unwrap(), ["first", "second"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn changes_already_started_at_the_deadline_are_left_to_finish() {
+        let scribe = Scribe::default();
+        let registry = ToolRegistry::new().register(scribe.clone());
+        let calls = vec![
+            ("Scribe".to_string(), json!({ "ms": 2_000, "note": "slow" })),
+            ("Scribe".to_string(), json!({ "ms": 100, "note": "never" })),
+        ];
+
+        let results = registry.call_batch(&context(None), calls, Duration::from_secs(1)).await;
+        assert!(matches!(results[0], Err(Error::Unfinished(_))));
+        assert!(matches!(results[1], Err(Error::TimedOut(_))));
+
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        assert_eq!(*scribe.0.lock().unwrap(), ["slow"]);
+    }
+
+    #[tokio::test]
+    async fn mixed_batches_change_reminders_in_order() {
+        let mut ctx = context(None);
+        ctx.user = Some(crate::users::find_or_create_by_phone(&mut ctx.pool.get().unwrap(), "+358401234567").unwrap());
+        let registry = ToolRegistry::builtin();
+        registry
+            .call("StoreUserReminder", &ctx, json!({ "message": "old", "remind_at": "in 2 hours" }))
+            .await
+            .unwrap();
+
+        let calls = vec![
+            ("DeleteAllReminders".to_string(), json!({})),
+            ("StoreUserReminder".to_string(), json!({ "message": "new", "remind_at": "in 3 hours" })),
+            ("GetUserReminders".to_string(), json!({})),
+        ];
+        let results = registry.call_batch(&ctx, calls, Duration::from_secs(10)).await;
+        assert!(results.iter().all(Result::is_ok), "{:?}", results);
+        let ToolCallResponse::Listing(listed) = results[2].as_ref().unwrap() else {
+            panic!("{:?}", results[2]);
+        };
+        let listed: Vec<&str> = listed.iter().map(|listing| listing.reminder.message.as_str()).collect();
+        assert_eq!(listed, ["new"]);
+
+        let left = crate::reminders::list_reminders(&mut ctx.pool.get().unwrap(), ctx.user.as_ref().unwrap()).unwrap();
+        let messages: Vec<&str> = left.iter().map(|reminder| reminder.message.as_str()).collect();
+        assert_eq!(messages, ["new"]);
+    }
+
+    #[test]
+    fn definitions_describe_every_tool() {
+        let definitions = serde_json::to_value(ToolRegistry::builtin().definitions(Some("https://example.com/"))).unwrap();
+        let store = &definitions[0];
+        assert_eq!(store["type"], "function");
+        assert_eq!(store["server"]["url"], "https://example.com/tool-call");
+        assert_eq!(store["function"]["name"], "StoreUserReminder");
+
+        let parameters = &store["function"]["parameters"];
+        assert_eq!(parameters["type"], "object");
+        assert_eq!(parameters["required"], json!(["message", "remind_at"]));
+        assert_eq!(parameters["additionalProperties"], false);
+        assert_eq!(parameters["properties"]["remind_at"]["type"], "string");
+        assert_eq!(
+            parameters["properties"]["recurrence"]["properties"]["frequency"]["enum"],
+            json!(["daily", "weekly", "monthly"])
+        );
+        assert!(parameters.get("definitions").is_none());
+
+        let list = definitions.as_array().unwrap().iter().find(|d| d["function"]["name"] == "GetUserReminders").unwrap();
+        assert_eq!(list["function"]["parameters"]["properties"], json!({}));
+        assert!(ToolRegistry::builtin().definitions(None)[0].server.is_none());
+    }
+
+    #[test]
+    fn arguments_may_arrive_as_a_json_string() {
+        let parsed: Value = parse_arguments(Value::String(r#"{"id": "abc"}"#.to_string())).unwrap();
+        assert_eq!(parsed, json!({ "id": "abc" }));
+        assert!(parse_arguments::<NoArguments>(Value::Null).is_ok());
+        assert!(matches!(
+            parse_arguments::<NoArguments>(Value::String("{".to_string())),
+            Err(Error::InvalidArguments(_))
+        ));
+    }
+}
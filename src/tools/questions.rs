@@ -0,0 +1,116 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use crate::answer_cache;
+use crate::answers::{self, Answer, AnswerError};
+use crate::error::Error;
+use crate::models::ToolCallResponse;
+use super::{parse_arguments, schema_of, Tool, ToolContext};
+
+
+/// Answers longer than this are also texted to the user, since they are hard to follow by ear.
+const SMS_ANSWER_THRESHOLD: usize = 300;
+
+pub struct AskQuestion;
+
+#[derive(Deserialize, JsonSchema, Debug)]
+#[serde(deny_unknown_fields)]
+struct QuestionArgs {
+    /// The caller's question
+    #[serde(alias = "question")]
+    message: String,
+}
+
+#[async_trait]
+impl Tool for AskQuestion {
+    fn name(&self) -> &'static str {
+        "AskQuestion"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        // the tool's old name, still used by assistants set up before
+        &["AskPerplexity"]
+    }
+
+    fn description(&self) -> &'static str {
+        "Looks up the answer to a question on the web. Long answers are also texted to the caller."
+    }
+
+    fn parameters(&self) -> Value {
+        schema_of::<QuestionArgs>()
+    }
+
+    fn read_only(&self) -> bool {
+        // the answer cache it fills is not something the caller sees change
+        true
+    }
+
+    async fn execute(&self, ctx: &ToolContext, arguments: Value) -> Result<ToolCallResponse, Error> {
+        let args: QuestionArgs = parse_arguments(arguments)?;
+        tracing::info!("Answering question");
+        match answer_question(ctx, &args.message).await {
+            Ok(answer) => {
+                if let (Some(user), true) = (&ctx.user, answer.text.len() > SMS_ANSWER_THRESHOLD) {
+                    text_answer(ctx, &user.phone_number, answer.with_sources());
+                }
+                Ok(ToolCallResponse::Message(answer.text))
+            }
+            Err(e) => {
+                tracing::error!("No answer for the caller: {}", e);
+                Ok(ToolCallResponse::Message(answers::APOLOGY.to_string()))
+            }
+        }
+    }
+}
+
+/// Answers from the cache when someone in the same timezone with the same provider
+/// asked the same thing recently, otherwise asks the providers and caches what they
+/// say. The cache is only an optimisation, so its failures are logged and skipped.
+async fn answer_question(ctx: &ToolContext, question: &str) -> Result<Answer, AnswerError> {
+    let locale = cache_locale(ctx);
+    let asked = question.to_string();
+    let looked_up = locale.clone();
+    let cached = ctx
+        .db(move |conn| answer_cache::lookup(conn, &asked, &looked_up, Utc::now()))
+        .await;
+    match cached {
+        Ok(Some(answer)) => {
+            tracing::info!("Answered from cache");
+            return Ok(answer);
+        }
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Could not read the answer cache: {}", e),
+    }
+
+    let answer = ctx.answers.ask(ctx.user.as_ref(), question).await?;
+    let (asked, cached) = (question.to_string(), answer.clone());
+    let stored = ctx
+        .db(move |conn| answer_cache::store(conn, &asked, &locale, &cached, Utc::now()))
+        .await;
+    if let Err(e) = stored {
+        tracing::warn!("Could not cache the answer: {}", e);
+    }
+    Ok(answer)
+}
+
+/// What the cache keys answers by besides the question: the caller's timezone and
+/// the provider they would be answered by, so choosing another provider gets a
+/// fresh answer rather than the old one's.
+fn cache_locale(ctx: &ToolContext) -> String {
+    let provider = ctx.answers.for_user(ctx.user.as_ref());
+    let provider = provider.as_ref().map_or("none", |provider| provider.name());
+    format!("{}/{}", ctx.timezone().name(), provider)
+}
+
+/// Texts the full answer in the background so the caller isn't kept waiting.
+fn text_answer(ctx: &ToolContext, to: &str, answer: String) {
+    let to = to.to_string();
+    let sms = ctx.sms.clone();
+    tokio::spawn(async move {
+        if let Err(e) = sms.send(&to, &answer).await {
+            tracing::warn!("Could not text the answer: {}", e);
+        }
+    });
+}
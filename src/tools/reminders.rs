@@ -0,0 +1,429 @@
+use async_trait::async_trait;
+use chrono::{DateTime, TimeDelta, Utc};
+use chrono_tz::Tz;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use crate::error::Error;
+use crate::models::{ReminderListing, ToolCallResponse};
+use crate::recurrence::Recurrence;
+use crate::reminders;
+use crate::times;
+use super::{parse_arguments, schema_of, unknown_caller, NoArguments, Tool, ToolContext};
+
+
+pub struct StoreUserReminder;
+pub struct GetUserReminders;
+pub struct DeleteAllReminders;
+pub struct DeleteReminder;
+pub struct UpdateReminder;
+pub struct SnoozeReminder;
+
+#[derive(Deserialize, JsonSchema, Debug)]
+#[serde(deny_unknown_fields)]
+struct CreateReminderArgs {
+    /// What to remind the caller about
+    message: String,
+    /// When, as an ISO 8601 time or the caller's own words, e.g. "tomorrow at 9"
+    remind_at: String,
+    /// How the reminder repeats, if it does
+    recurrence: Option<Recurrence>,
+}
+
+#[derive(Deserialize, JsonSchema, Debug)]
+#[serde(deny_unknown_fields)]
+struct ReminderIdArgs {
+    /// Id of the reminder, from GetUserReminders
+    id: String,
+}
+
+#[derive(Deserialize, JsonSchema, Debug)]
+#[serde(deny_unknown_fields)]
+struct UpdateReminderArgs {
+    /// Id of the reminder, from GetUserReminders
+    id: String,
+    /// New text, if it changes
+    message: Option<String>,
+    /// New time, if it changes
+    remind_at: Option<String>,
+}
+
+#[derive(Deserialize, JsonSchema, Debug)]
+#[serde(deny_unknown_fields)]
+struct SnoozeReminderArgs {
+    /// Id of the reminder, from GetUserReminders
+    id: String,
+    /// How many minutes from now, at most a year
+    #[schemars(range(min = 1, max = 525_600))]
+    minutes: i64,
+}
+
+/// Snoozing further than a year is surely a misheard number.
+const MAX_SNOOZE_MINUTES: u32 = 525_600;
+
+fn reminder_not_found() -> ToolCallResponse {
+    ToolCallResponse::Message(
+        "I couldn't find that reminder. Maybe list the reminders first?".to_string(),
+    )
+}
+
+/// Checks the new reminder's arguments and works out when it is due, or says what
+/// the assistant should ask the caller about.
+fn resolve_new_reminder(
+    args: &CreateReminderArgs,
+    timezone: Tz,
+    sent_at: DateTime<Utc>,
+) -> Result<DateTime<Utc>, String> {
+    if let Some(rule) = &args.recurrence {
+        rule.validate()?;
+    }
+    times::parse_remind_at(&args.remind_at, timezone, sent_at).map_err(|e| e.to_string())
+}
+
+fn resolve_optional_time(
+    remind_at: Option<&str>,
+    timezone: Tz,
+    sent_at: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, String> {
+    remind_at
+        .map(|at| times::parse_remind_at(at, timezone, sent_at).map_err(|e| e.to_string()))
+        .transpose()
+}
+
+#[async_trait]
+impl Tool for StoreUserReminder {
+    fn name(&self) -> &'static str {
+        "StoreUserReminder"
+    }
+
+    fn description(&self) -> &'static str {
+        "Stores a reminder that is sent to the caller when it is due, optionally repeating."
+    }
+
+    fn parameters(&self) -> Value {
+        schema_of::<CreateReminderArgs>()
+    }
+
+    async fn execute(&self, ctx: &ToolContext, arguments: Value) -> Result<ToolCallResponse, Error> {
+        let args: CreateReminderArgs = parse_arguments(arguments)?;
+        let Some(user) = &ctx.user else {
+            return Ok(unknown_caller());
+        };
+        let remind_at = match resolve_new_reminder(&args, ctx.timezone(), ctx.sent_at) {
+            Ok(remind_at) => remind_at,
+            Err(problem) => return Ok(ToolCallResponse::Message(problem)),
+        };
+        tracing::info!("Creating reminder due {}", remind_at);
+        let user = user.clone();
+        let tz = ctx.timezone();
+        let reminder = ctx
+            .db(move |conn| reminders::create_reminder(conn, &user, &args.message, remind_at, args.recurrence.as_ref(), tz))
+            .await?;
+        Ok(ToolCallResponse::Single(reminder))
+    }
+}
+
+#[async_trait]
+impl Tool for GetUserReminders {
+    fn name(&self) -> &'static str {
+        "GetUserReminders"
+    }
+
+    fn description(&self) -> &'static str {
+        "Lists the caller's reminders in their local time, with the next few times repeating ones go off."
+    }
+
+    fn parameters(&self) -> Value {
+        schema_of::<NoArguments>()
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, ctx: &ToolContext, arguments: Value) -> Result<ToolCallResponse, Error> {
+        let NoArguments {} = parse_arguments(arguments)?;
+        let Some(user) = &ctx.user else {
+            return Ok(unknown_caller());
+        };
+        tracing::info!("Listing all reminders");
+        let user = user.clone();
+        let tz = ctx.timezone();
+        let listing = ctx
+            .db(move |conn| reminders::list_reminders(conn, &user))
+            .await?
+            .into_iter()
+            .map(|reminder| ReminderListing::new(reminder, tz))
+            .collect();
+        Ok(ToolCallResponse::Listing(listing))
+    }
+}
+
+#[async_trait]
+impl Tool for DeleteAllReminders {
+    fn name(&self) -> &'static str {
+        "DeleteAllReminders"
+    }
+
+    fn description(&self) -> &'static str {
+        "Deletes every reminder the caller has."
+    }
+
+    fn parameters(&self) -> Value {
+        schema_of::<NoArguments>()
+    }
+
+    async fn execute(&self, ctx: &ToolContext, arguments: Value) -> Result<ToolCallResponse, Error> {
+        let NoArguments {} = parse_arguments(arguments)?;
+        let Some(user) = &ctx.user else {
+            return Ok(unknown_caller());
+        };
+        let user = user.clone();
+        let deleted_count = ctx.db(move |conn| reminders::delete_all_reminders(conn, &user)).await?;
+        tracing::info!("Deleted {} reminders", deleted_count);
+        Ok(ToolCallResponse::Multiple(Vec::new())) // Return empty vector after deletion
+    }
+}
+
+#[async_trait]
+impl Tool for DeleteReminder {
+    fn name(&self) -> &'static str {
+        "DeleteReminder"
+    }
+
+    fn description(&self) -> &'static str {
+        "Deletes one of the caller's reminders."
+    }
+
+    fn parameters(&self) -> Value {
+        schema_of::<ReminderIdArgs>()
+    }
+
+    async fn execute(&self, ctx: &ToolContext, arguments: Value) -> Result<ToolCallResponse, Error> {
+        let args: ReminderIdArgs = parse_arguments(arguments)?;
+        let Some(user) = &ctx.user else {
+            return Ok(unknown_caller());
+        };
+        tracing::info!("Deleting reminder {}", args.id);
+        let user = user.clone();
+        let deleted = ctx.db(move |conn| reminders::delete_reminder(conn, &user, &args.id)).await?;
+        Ok(deleted.map_or_else(reminder_not_found, ToolCallResponse::Single))
+    }
+}
+
+#[async_trait]
+impl Tool for UpdateReminder {
+    fn name(&self) -> &'static str {
+        "UpdateReminder"
+    }
+
+    fn description(&self) -> &'static str {
+        "Changes the message or time of one of the caller's reminders."
+    }
+
+    fn parameters(&self) -> Value {
+        schema_of::<UpdateReminderArgs>()
+    }
+
+    async fn execute(&self, ctx: &ToolContext, arguments: Value) -> Result<ToolCallResponse, Error> {
+        let args: UpdateReminderArgs = parse_arguments(arguments)?;
+        let Some(user) = &ctx.user else {
+            return Ok(unknown_caller());
+        };
+        let new_remind_at = match resolve_optional_time(args.remind_at.as_deref(), ctx.timezone(), ctx.sent_at) {
+            Ok(new_remind_at) => new_remind_at,
+            Err(problem) => return Ok(ToolCallResponse::Message(problem)),
+        };
+        tracing::info!("Updating reminder {}", args.id);
+        let user = user.clone();
+        let tz = ctx.timezone();
+        let updated = ctx
+            .db(move |conn| reminders::update_reminder(conn, &user, &args.id, args.message.as_deref(), new_remind_at, tz))
+            .await?;
+        Ok(updated.map_or_else(reminder_not_found, ToolCallResponse::Single))
+    }
+}
+
+#[async_trait]
+impl Tool for SnoozeReminder {
+    fn name(&self) -> &'static str {
+        "SnoozeReminder"
+    }
+
+    fn description(&self) -> &'static str {
+        "Pushes one of the caller's reminders a number of minutes from now, even if it already went off."
+    }
+
+    fn parameters(&self) -> Value {
+        schema_of::<SnoozeReminderArgs>()
+    }
+
+    async fn execute(&self, ctx: &ToolContext, arguments: Value) -> Result<ToolCallResponse, Error> {
+        let args: SnoozeReminderArgs = parse_arguments(arguments)?;
+        let Some(user) = &ctx.user else {
+            return Ok(unknown_caller());
+        };
+        let until = u32::try_from(args.minutes)
+            .ok()
+            .filter(|minutes| (1..=MAX_SNOOZE_MINUTES).contains(minutes))
+            .and_then(|minutes| ctx.sent_at.checked_add_signed(TimeDelta::minutes(minutes.into())));
+        let Some(until) = until else {
+            return Ok(ToolCallResponse::Message(
+                "I can snooze a reminder for anything from a minute to a year. For how long should I snooze it?".to_string(),
+            ));
+        };
+        tracing::info!("Snoozing reminder {} for {} minutes", args.id, args.minutes);
+        let user = user.clone();
+        let tz = ctx.timezone();
+        let snoozed = ctx
+            .db(move |conn| reminders::snooze_reminder(conn, &user, &args.id, until, tz))
+            .await?;
+        Ok(snoozed.map_or_else(reminder_not_found, ToolCallResponse::Single))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::tools::tests::context;
+    use crate::tools::ToolRegistry;
+
+    #[tokio::test]
+    async fn stores_lists_and_deletes_for_the_caller() {
+        let mut ctx = context(None);
+        let user = crate::users::find_or_create_by_phone(&mut ctx.pool.get().unwrap(), "+358401234567").unwrap();
+        ctx.user = Some(user);
+        let registry = ToolRegistry::builtin();
+
+        let stored = registry
+            .call("StoreUserReminder", &ctx, json!({ "message": "take pills", "remind_at": "in 2 hours" }))
+            .await
+            .unwrap();
+        let ToolCallResponse::Single(reminder) = stored else {
+            panic!("expected the stored reminder, got {:?}", stored);
+        };
+
+        let listed = registry.call("GetUserReminders", &ctx, json!({})).await.unwrap();
+        let ToolCallResponse::Listing(listing) = listed else {
+            panic!("expected a listing, got {:?}", listed);
+        };
+        assert_eq!(listing.len(), 1);
+        assert_eq!(listing[0].timezone, "Europe/Helsinki");
+
+        registry.call("DeleteReminder", &ctx, json!({ "id": reminder.id })).await.unwrap();
+        let missing = registry.call("DeleteReminder", &ctx, json!({ "id": reminder.id })).await.unwrap();
+        assert!(matches!(missing, ToolCallResponse::Message(_)));
+    }
+
+    fn message(response: ToolCallResponse) -> String {
+        match response {
+            ToolCallResponse::Message(message) => message,
+            other => panic!("expected a message, got {:?}", other),
+        }
+    }
+
+    fn single(response: ToolCallResponse) -> crate::models::Reminder {
+        match response {
+            ToolCallResponse::Single(reminder) => reminder,
+            other => panic!("expected a reminder, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn changes_only_the_callers_own_reminders() {
+        let mut owner = context(None);
+        let mut conn = owner.pool.get().unwrap();
+        owner.user = Some(crate::users::find_or_create_by_phone(&mut conn, "+358401234567").unwrap());
+        let mut stranger = owner.clone();
+        stranger.user = Some(crate::users::find_or_create_by_phone(&mut conn, "+358509876543").unwrap());
+        drop(conn);
+        let registry = ToolRegistry::builtin();
+
+        let stored = single(
+            registry
+                .call("StoreUserReminder", &owner, json!({ "message": "take pills", "remind_at": "in 2 hours" }))
+                .await
+                .unwrap(),
+        );
+        let id = json!(stored.id);
+
+        for (name, arguments) in [
+            ("UpdateReminder", json!({ "id": id, "message": "mine now" })),
+            ("SnoozeReminder", json!({ "id": id, "minutes": 5 })),
+            ("DeleteReminder", json!({ "id": id })),
+        ] {
+            let refused = message(registry.call(name, &stranger, arguments).await.unwrap());
+            assert!(refused.starts_with("I couldn't find that reminder"), "{}: {}", name, refused);
+        }
+        let unknown = registry.call("UpdateReminder", &owner, json!({ "id": "nope", "message": "x" })).await.unwrap();
+        assert!(message(unknown).starts_with("I couldn't find that reminder"));
+        let unknown = registry.call("SnoozeReminder", &owner, json!({ "id": "nope", "minutes": 5 })).await.unwrap();
+        assert!(message(unknown).starts_with("I couldn't find that reminder"));
+
+        let updated = single(
+            registry
+                .call("UpdateReminder", &owner, json!({ "id": id, "message": "take the blue pills", "remind_at": "in 3 hours" }))
+                .await
+                .unwrap(),
+        );
+        assert_eq!(updated.message, "take the blue pills");
+        assert!(updated.remind_at > stored.remind_at);
+
+        // counted from when the caller asked, like every other relative time
+        owner.sent_at -= TimeDelta::minutes(5);
+        let snoozed = single(registry.call("SnoozeReminder", &owner, json!({ "id": id, "minutes": 30 })).await.unwrap());
+        assert_eq!(snoozed.into_datetime(), owner.sent_at + TimeDelta::minutes(30));
+        assert_eq!(snoozed.message, "take the blue pills");
+
+        let deleted = single(registry.call("DeleteReminder", &owner, json!({ "id": id })).await.unwrap());
+        assert_eq!(deleted.id, stored.id);
+    }
+
+    #[tokio::test]
+    async fn snoozes_for_a_minute_to_a_year() {
+        let mut ctx = context(None);
+        ctx.user = Some(crate::users::find_or_create_by_phone(&mut ctx.pool.get().unwrap(), "+358401234567").unwrap());
+        let registry = ToolRegistry::builtin();
+        let stored = single(
+            registry
+                .call("StoreUserReminder", &ctx, json!({ "message": "stretch", "remind_at": "in 2 hours" }))
+                .await
+                .unwrap(),
+        );
+
+        for minutes in [json!(0), json!(-5), json!(525_601), json!(i64::MAX)] {
+            let refused = registry.call("SnoozeReminder", &ctx, json!({ "id": stored.id, "minutes": minutes })).await.unwrap();
+            assert!(message(refused).starts_with("I can snooze a reminder for anything from a minute to a year"), "{}", minutes);
+        }
+        let listed = crate::reminders::find_reminder(&mut ctx.pool.get().unwrap(), ctx.user.as_ref().unwrap(), &stored.id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(listed.remind_at, stored.remind_at);
+
+        let a_year = registry.call("SnoozeReminder", &ctx, json!({ "id": stored.id, "minutes": 525_600 })).await.unwrap();
+        assert_eq!(single(a_year).into_datetime(), ctx.sent_at + TimeDelta::days(365));
+    }
+
+    #[test]
+    fn moving_a_repeating_reminder_moves_the_series_but_snoozing_does_not() {
+        use chrono::TimeZone;
+
+        let mut conn = crate::test_connection();
+        let user = crate::users::find_or_create_by_phone(&mut conn, "+358401234567").unwrap();
+        let daily: Recurrence = "FREQ=DAILY".parse().unwrap();
+        let eight = Utc.with_ymd_and_hms(2099, 1, 7, 8, 0, 0).unwrap();
+        let stored = reminders::create_reminder(&mut conn, &user, "pills", eight, Some(&daily), Tz::UTC).unwrap();
+        assert_eq!(stored.recurrence.as_deref(), Some("FREQ=DAILY;BYHOUR=8;BYMINUTE=0"));
+
+        let rule = |conn: &mut diesel::SqliteConnection| {
+            reminders::find_reminder(conn, &user, &stored.id).unwrap().unwrap().recurrence.unwrap()
+        };
+        reminders::snooze_reminder(&mut conn, &user, &stored.id, eight + TimeDelta::minutes(10), Tz::UTC).unwrap();
+        assert_eq!(rule(&mut conn), "FREQ=DAILY;BYHOUR=8;BYMINUTE=0");
+
+        let half_past_nine = eight + TimeDelta::minutes(90);
+        reminders::update_reminder(&mut conn, &user, &stored.id, None, Some(half_past_nine), Tz::UTC).unwrap();
+        assert_eq!(rule(&mut conn), "FREQ=DAILY;BYHOUR=9;BYMINUTE=30");
+    }
+}
@@ -0,0 +1,51 @@
+use async_trait::async_trait;
+use chrono_tz::Tz;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use crate::error::Error;
+use crate::models::ToolCallResponse;
+use crate::users;
+use super::{parse_arguments, schema_of, unknown_caller, Tool, ToolContext};
+
+
+pub struct SetUserTimezone;
+
+#[derive(Deserialize, JsonSchema, Debug)]
+#[serde(deny_unknown_fields)]
+struct TimezoneArgs {
+    /// IANA timezone name, e.g. Europe/Helsinki
+    timezone: String,
+}
+
+#[async_trait]
+impl Tool for SetUserTimezone {
+    fn name(&self) -> &'static str {
+        "SetUserTimezone"
+    }
+
+    fn description(&self) -> &'static str {
+        "Remembers the timezone the caller lives in, used for every time they mention."
+    }
+
+    fn parameters(&self) -> Value {
+        schema_of::<TimezoneArgs>()
+    }
+
+    async fn execute(&self, ctx: &ToolContext, arguments: Value) -> Result<ToolCallResponse, Error> {
+        let args: TimezoneArgs = parse_arguments(arguments)?;
+        let Some(user) = &ctx.user else {
+            return Ok(unknown_caller());
+        };
+        let Ok(tz) = args.timezone.trim().parse::<Tz>() else {
+            return Ok(ToolCallResponse::Message(format!(
+                "I don't know the timezone \"{}\". Which city are you in?",
+                args.timezone
+            )));
+        };
+        tracing::info!("Setting timezone of user {} to {}", user.id, tz);
+        let user = user.clone();
+        ctx.db(move |conn| users::set_timezone(conn, &user, tz)).await?;
+        Ok(ToolCallResponse::Message(format!("Got it, I'll use {} time from now on.", tz)))
+    }
+}
@@ -0,0 +1,114 @@
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use crate::models::User;
+
+
+/// Strips the formatting people and carriers put into numbers, so `+358 40-123 4567`
+/// and `+358401234567` are the same caller.
+pub fn normalize_phone_number(raw: &str) -> String {
+    raw.chars()
+        .filter(|c| c.is_ascii_digit() || *c == '+')
+        .collect()
+}
+
+/// Looks up the caller by phone number, registering them on their first call.
+pub fn find_or_create_by_phone(
+    conn: &mut SqliteConnection,
+    raw_number: &str,
+) -> Result<User, diesel::result::Error> {
+    use crate::schema::users::dsl::*;
+
+    let number = normalize_phone_number(raw_number);
+    diesel::insert_or_ignore_into(users)
+        .values(User::new(number.clone()))
+        .execute(conn)?;
+    users.filter(phone_number.eq(number)).first::<User>(conn)
+}
+
+/// The caller with this number, if they have ever called.
+pub fn find_by_phone(
+    conn: &mut SqliteConnection,
+    raw_number: &str,
+) -> Result<Option<User>, diesel::result::Error> {
+    use crate::schema::users::dsl::*;
+
+    users
+        .filter(phone_number.eq(normalize_phone_number(raw_number)))
+        .first::<User>(conn)
+        .optional()
+}
+
+/// Everyone who has called, oldest first.
+pub fn list_users(conn: &mut SqliteConnection) -> Result<Vec<User>, diesel::result::Error> {
+    use crate::schema::users::dsl::*;
+
+    users.order(created_at.asc()).load::<User>(conn)
+}
+
+/// Remembers the zone the caller speaks times in.
+pub fn set_timezone(
+    conn: &mut SqliteConnection,
+    user: &User,
+    tz: Tz,
+) -> Result<User, diesel::result::Error> {
+    use crate::schema::users::dsl::*;
+
+    diesel::update(users.find(&user.id))
+        .set(timezone.eq(tz.name()))
+        .execute(conn)?;
+    users.find(&user.id).first::<User>(conn)
+}
+
+/// Routes the caller's questions to the named answer provider, or back to the
+/// default with `None`.
+pub fn set_answer_provider(
+    conn: &mut SqliteConnection,
+    user: &User,
+    provider: Option<&str>,
+) -> Result<User, diesel::result::Error> {
+    use crate::schema::users::dsl::*;
+
+    diesel::update(users.find(&user.id))
+        .set(answer_provider.eq(provider))
+        .execute(conn)?;
+    users.find(&user.id).first::<User>(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_number_resolves_to_same_user() {
+        let mut conn = crate::test_connection();
+        let first = find_or_create_by_phone(&mut conn, "+358 40-123 4567").unwrap();
+        let second = find_or_create_by_phone(&mut conn, "+358401234567").unwrap();
+        let other = find_or_create_by_phone(&mut conn, "+358509876543").unwrap();
+
+        assert_eq!(first.id, second.id);
+        assert_eq!(second.phone_number, "+358401234567");
+        assert_ne!(first.id, other.id);
+    }
+
+    #[test]
+    fn timezone_falls_back_until_set() {
+        let mut conn = crate::test_connection();
+        let user = find_or_create_by_phone(&mut conn, "+358401234567").unwrap();
+        assert_eq!(user.timezone(Tz::Europe__Helsinki), Tz::Europe__Helsinki);
+
+        let user = set_timezone(&mut conn, &user, Tz::America__New_York).unwrap();
+        assert_eq!(user.timezone.as_deref(), Some("America/New_York"));
+        assert_eq!(user.timezone(Tz::Europe__Helsinki), Tz::America__New_York);
+    }
+
+    #[test]
+    fn finds_callers_without_registering_them() {
+        let mut conn = crate::test_connection();
+        assert!(find_by_phone(&mut conn, "+358401234567").unwrap().is_none());
+
+        let user = find_or_create_by_phone(&mut conn, "+358401234567").unwrap();
+        let found = find_by_phone(&mut conn, "+358 40 123 4567").unwrap().unwrap();
+        assert_eq!(found.id, user.id);
+        assert_eq!(list_users(&mut conn).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,312 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+use crate::config::Config;
+use crate::server::read_body;
+
+
+/// Header the voice platform repeats the server secret in.
+pub const SECRET_HEADER: &str = "x-vapi-secret";
+/// Hex HMAC-SHA256 of `"{timestamp}.{body}"`, optionally prefixed with `sha256=`.
+pub const SIGNATURE_HEADER: &str = "x-vapi-signature";
+/// Unix seconds when the request was signed.
+pub const TIMESTAMP_HEADER: &str = "x-vapi-timestamp";
+
+/// How far a signed request's timestamp may be from our clock before it counts as
+/// replayed.
+const DEFAULT_TOLERANCE: Duration = Duration::from_secs(5 * 60);
+
+/// Checks that webhook requests come from the voice platform: by the shared secret
+/// in [`SECRET_HEADER`], and when a signing secret is set also by a signature over
+/// the body and a recent timestamp. With neither configured every request passes.
+#[derive(Clone, Default)]
+pub struct WebhookAuth {
+    secret: Option<Arc<str>>,
+    signing_secret: Option<Arc<[u8]>>,
+    tolerance: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    MissingSecret,
+    WrongSecret,
+    MissingSignature,
+    BadSignature,
+    /// The timestamp is missing, unreadable or outside the tolerance.
+    Stale,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::MissingSecret => write!(f, "missing {} header", SECRET_HEADER),
+            Rejection::WrongSecret => write!(f, "wrong webhook secret"),
+            Rejection::MissingSignature => write!(f, "missing {} header", SIGNATURE_HEADER),
+            Rejection::BadSignature => write!(f, "signature does not match the body"),
+            Rejection::Stale => write!(f, "missing or stale {} header", TIMESTAMP_HEADER),
+        }
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        (StatusCode::UNAUTHORIZED, self.to_string()).into_response()
+    }
+}
+
+impl WebhookAuth {
+    pub fn new() -> Self {
+        WebhookAuth { tolerance: DEFAULT_TOLERANCE, ..WebhookAuth::default() }
+    }
+
+    pub fn with_secret(mut self, secret: &str) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    pub fn with_signing_secret(mut self, signing_secret: &str) -> Self {
+        self.signing_secret = Some(signing_secret.as_bytes().into());
+        self
+    }
+
+    pub fn with_tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// The `[webhook]` secrets, each optional.
+    pub fn from_config(config: &Config) -> Self {
+        let webhook = &config.webhook;
+        let mut auth = WebhookAuth::new().with_tolerance(Duration::from_secs(webhook.tolerance_secs));
+        if let Some(secret) = &webhook.secret {
+            auth = auth.with_secret(secret);
+        }
+        if let Some(signing_secret) = &webhook.signing_secret {
+            auth = auth.with_signing_secret(signing_secret);
+        }
+        auth
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.secret.is_some() || self.signing_secret.is_some()
+    }
+
+    /// Decides whether a request with these headers and body, received at `now`
+    /// (unix seconds), is genuine.
+    pub fn check(&self, headers: &HeaderMap, body: &[u8], now: i64) -> Result<(), Rejection> {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+        if let Some(secret) = &self.secret {
+            let given = header(SECRET_HEADER).ok_or(Rejection::MissingSecret)?;
+            if !bool::from(given.as_bytes().ct_eq(secret.as_bytes())) {
+                return Err(Rejection::WrongSecret);
+            }
+        }
+
+        if let Some(signing_secret) = &self.signing_secret {
+            let timestamp: i64 = header(TIMESTAMP_HEADER)
+                .and_then(|timestamp| timestamp.trim().parse().ok())
+                .ok_or(Rejection::Stale)?;
+            if timestamp.abs_diff(now) > self.tolerance.as_secs() {
+                return Err(Rejection::Stale);
+            }
+            let signature = header(SIGNATURE_HEADER).ok_or(Rejection::MissingSignature)?;
+            let signature = signature.trim();
+            let signature = hex::decode(signature.strip_prefix("sha256=").unwrap_or(signature))
+                .map_err(|_| Rejection::BadSignature)?;
+            // verify_slice compares in constant time
+            signing_mac(signing_secret, timestamp, body)
+                .verify_slice(&signature)
+                .map_err(|_| Rejection::BadSignature)?;
+        }
+        Ok(())
+    }
+
+    /// Headers that get a request with this body, sent at `now`, past [`Self::check`],
+    /// for sending webhooks to ourselves.
+    pub fn credentials(&self, body: &[u8], now: i64) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+        if let Some(secret) = &self.secret {
+            headers.push((SECRET_HEADER, secret.to_string()));
+        }
+        if let Some(signing_secret) = &self.signing_secret {
+            let signature = signing_mac(signing_secret, now, body).finalize().into_bytes();
+            headers.push((TIMESTAMP_HEADER, now.to_string()));
+            headers.push((SIGNATURE_HEADER, hex::encode(signature)));
+        }
+        headers
+    }
+}
+
+fn signing_mac(signing_secret: &[u8], timestamp: i64, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(signing_secret).expect("HMAC takes keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// Middleware turning away webhook requests that fail [`WebhookAuth::check`] with 401,
+/// and bodies it can't read in full with what [`read_body`] answers.
+pub async fn verify_webhook(State(auth): State<WebhookAuth>, req: Request, next: Next) -> Response {
+    if !auth.is_enabled() {
+        return next.run(req).await;
+    }
+    let (parts, body) = req.into_parts();
+    let bytes = match read_body(body).await {
+        Ok(bytes) => bytes,
+        Err(response) => return response,
+    };
+    if let Err(rejection) = auth.check(&parts.headers, &bytes, Utc::now().timestamp()) {
+        tracing::warn!("Rejected webhook to {}: {}", parts.uri.path(), rejection);
+        return rejection.into_response();
+    }
+    next.run(Request::from_parts(parts, Body::from(bytes))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Bytes;
+    use axum::middleware;
+    use axum::routing::post;
+    use axum::Router;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    const NOW: i64 = 1_736_500_000;
+    const BODY: &[u8] = br#"{"message":{"toolCalls":[]}}"#;
+
+    fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn headers(pairs: &[(&'static str, String)]) -> HeaderMap {
+        pairs.iter().map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap())).collect()
+    }
+
+    #[test]
+    fn checks_the_shared_secret() {
+        let auth = WebhookAuth::new().with_secret("hunter2");
+        assert_eq!(auth.check(&headers(&[(SECRET_HEADER, "hunter2".into())]), BODY, NOW), Ok(()));
+        assert_eq!(auth.check(&headers(&[(SECRET_HEADER, "hunter3".into())]), BODY, NOW), Err(Rejection::WrongSecret));
+        assert_eq!(auth.check(&headers(&[(SECRET_HEADER, "hunter".into())]), BODY, NOW), Err(Rejection::WrongSecret));
+        assert_eq!(auth.check(&HeaderMap::new(), BODY, NOW), Err(Rejection::MissingSecret));
+    }
+
+    #[test]
+    fn checks_signatures_and_their_age() {
+        let auth = WebhookAuth::new().with_signing_secret("signing");
+        let signed = |timestamp: i64, signature: String| {
+            headers(&[(TIMESTAMP_HEADER, timestamp.to_string()), (SIGNATURE_HEADER, signature)])
+        };
+
+        assert_eq!(auth.check(&signed(NOW, sign("signing", NOW, BODY)), BODY, NOW), Ok(()));
+        let prefixed = format!("sha256={}", sign("signing", NOW - 60, BODY));
+        assert_eq!(auth.check(&signed(NOW - 60, prefixed), BODY, NOW), Ok(()));
+
+        let tampered = br#"{"message":{"toolCalls":[{"id":"x"}]}}"#;
+        assert_eq!(auth.check(&signed(NOW, sign("signing", NOW, BODY)), tampered, NOW), Err(Rejection::BadSignature));
+        assert_eq!(auth.check(&signed(NOW, sign("other", NOW, BODY)), BODY, NOW), Err(Rejection::BadSignature));
+        assert_eq!(auth.check(&signed(NOW, "not hex".into()), BODY, NOW), Err(Rejection::BadSignature));
+        // a captured request replayed later, even with its valid signature
+        let old = NOW - 10 * 60;
+        assert_eq!(auth.check(&signed(old, sign("signing", old, BODY)), BODY, NOW), Err(Rejection::Stale));
+        // the timestamp is signed too, so it can't be freshened
+        assert_eq!(auth.check(&signed(NOW, sign("signing", old, BODY)), BODY, NOW), Err(Rejection::BadSignature));
+        assert_eq!(
+            auth.check(&headers(&[(SIGNATURE_HEADER, sign("signing", NOW, BODY))]), BODY, NOW),
+            Err(Rejection::Stale)
+        );
+        assert_eq!(
+            auth.check(&headers(&[(TIMESTAMP_HEADER, NOW.to_string())]), BODY, NOW),
+            Err(Rejection::MissingSignature)
+        );
+    }
+
+    #[test]
+    fn signs_its_own_requests() {
+        let auth = WebhookAuth::new().with_secret("hunter2").with_signing_secret("signing");
+        let credentials = auth.credentials(BODY, NOW);
+        let headers = headers(&credentials);
+        assert_eq!(headers[SIGNATURE_HEADER], sign("signing", NOW, BODY).as_str());
+        assert_eq!(auth.check(&headers, BODY, NOW), Ok(()));
+        assert!(WebhookAuth::new().credentials(BODY, NOW).is_empty());
+    }
+
+    async fn status(auth: WebhookAuth, request: axum::http::request::Builder) -> StatusCode {
+        status_of(auth, request.body(Body::from(BODY)).unwrap()).await
+    }
+
+    async fn status_of(auth: WebhookAuth, mut request: Request) -> StatusCode {
+        let app = Router::new()
+            .route("/tool-call", post(|body: String| async move { body }))
+            .route_layer(middleware::from_fn_with_state(auth, verify_webhook));
+        *request.uri_mut() = "/tool-call".parse().unwrap();
+        *request.method_mut() = axum::http::Method::POST;
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        if status == StatusCode::OK {
+            // the handler still gets the body the signature was checked against
+            let echoed = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(&echoed[..], BODY);
+        }
+        status
+    }
+
+    #[tokio::test]
+    async fn middleware_turns_away_unauthenticated_requests() {
+        let now = Utc::now().timestamp();
+        let auth = WebhookAuth::new().with_secret("hunter2").with_signing_secret("signing");
+        let genuine = axum::http::Request::builder()
+            .header(SECRET_HEADER, "hunter2")
+            .header(TIMESTAMP_HEADER, now.to_string())
+            .header(SIGNATURE_HEADER, sign("signing", now, BODY));
+        assert_eq!(status(auth.clone(), genuine).await, StatusCode::OK);
+
+        let unsigned = axum::http::Request::builder().header(SECRET_HEADER, "hunter2");
+        assert_eq!(status(auth.clone(), unsigned).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(auth, axum::http::Request::builder()).await, StatusCode::UNAUTHORIZED);
+
+        assert_eq!(status(WebhookAuth::new(), axum::http::Request::builder()).await, StatusCode::OK);
+    }
+
+    /// A body whose sender hangs up halfway.
+    struct BrokenOff;
+
+    impl axum::body::HttpBody for BrokenOff {
+        type Data = Bytes;
+        type Error = std::io::Error;
+
+        fn poll_frame(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Option<Result<hyper::body::Frame<Bytes>, Self::Error>>> {
+            std::task::Poll::Ready(Some(Err(std::io::ErrorKind::ConnectionReset.into())))
+        }
+    }
+
+    #[tokio::test]
+    async fn middleware_reads_bounded_bodies_only() {
+        let auth = WebhookAuth::new().with_secret("hunter2");
+        let request = |body: Body| axum::http::Request::builder().header(SECRET_HEADER, "hunter2").body(body).unwrap();
+
+        let huge = vec![b' '; crate::server::MAX_BODY_BYTES + 1];
+        assert_eq!(status_of(auth.clone(), request(Body::from(huge))).await, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(status_of(auth, request(Body::new(BrokenOff))).await, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,101 @@
+//! Many callers at once against a real database file, the way the server runs.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use chrono::Utc;
+use chrono_tz::Tz;
+use diesel::SqliteConnection;
+use dumbassistant::answers::AnswerProviders;
+use dumbassistant::db::{self, Pool};
+use dumbassistant::models::ToolCallResponse;
+use dumbassistant::reminders;
+use dumbassistant::sms::LogSms;
+use dumbassistant::tools::{ToolContext, ToolRegistry};
+use dumbassistant::users;
+use serde_json::json;
+
+const CALLERS: usize = 16;
+
+/// How long the slow writer keeps the database locked.
+const HOLD: Duration = Duration::from_millis(800);
+
+fn migrated_pool(dir: &Path) -> Pool {
+    let pool = db::pool(dir.join("load.db").to_str().unwrap(), 8).unwrap();
+    db::run_pending_migrations(&mut pool.get().unwrap()).unwrap();
+    pool
+}
+
+async fn caller(pool: &Pool, n: usize) -> ToolContext {
+    let number = format!("+3584000000{:02}", n);
+    let user = db::run(pool, move |conn| users::find_or_create_by_phone(conn, &number)).await.unwrap();
+    let owner = user.clone();
+    db::run(pool, move |conn| reminders::create_reminder(conn, &owner, "water the plants", Utc::now(), None, Tz::UTC))
+        .await
+        .unwrap();
+    ToolContext {
+        pool: pool.clone(),
+        sms: Arc::new(LogSms),
+        answers: AnswerProviders::default(),
+        default_timezone: Tz::UTC,
+        user: Some(user),
+        sent_at: Utc::now(),
+    }
+}
+
+/// Makes the same tool call for every caller at once and waits for all of them.
+async fn all_at_once(callers: &[ToolContext], name: &'static str, arguments: serde_json::Value) -> Vec<ToolCallResponse> {
+    let registry = ToolRegistry::builtin();
+    let calls: Vec<_> = callers
+        .iter()
+        .map(|ctx| {
+            let (registry, ctx, arguments) = (registry.clone(), ctx.clone(), arguments.clone());
+            tokio::spawn(async move { registry.call(name, &ctx, arguments).await })
+        })
+        .collect();
+    let mut responses = Vec::new();
+    for call in calls {
+        responses.push(call.await.unwrap().unwrap());
+    }
+    responses
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn webhooks_do_not_queue_behind_a_slow_writer() {
+    let dir = tempfile::tempdir().unwrap();
+    let pool = migrated_pool(dir.path());
+    let mut callers = Vec::new();
+    for n in 0..CALLERS {
+        callers.push(caller(&pool, n).await);
+    }
+
+    // someone holds the write lock for a while, like a big write on a slow disk
+    let writer = {
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            db::run(&pool, |conn: &mut SqliteConnection| {
+                conn.immediate_transaction(|_| {
+                    std::thread::sleep(HOLD);
+                    Ok::<_, diesel::result::Error>(())
+                })
+            })
+            .await
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let started = Instant::now();
+    let listings = all_at_once(&callers, "GetUserReminders", json!({})).await;
+    let waited = started.elapsed();
+    assert!(waited < HOLD / 2, "{} callers listing their reminders took {:?}", CALLERS, waited);
+    assert!(!writer.is_finished(), "the writer let go before the readers were done");
+    for listing in listings {
+        assert!(matches!(listing, ToolCallResponse::Listing(listing) if listing.len() == 1));
+    }
+
+    // writers wait for the lock rather than failing with "database is locked"
+    let stored = all_at_once(&callers, "StoreUserReminder", json!({ "message": "feed the cat", "remind_at": "in 1 hour" })).await;
+    assert!(stored.iter().all(|response| matches!(response, ToolCallResponse::Single(_))));
+    assert!(started.elapsed() >= HOLD - Duration::from_millis(100));
+    writer.await.unwrap().unwrap();
+}
@@ -0,0 +1,438 @@
+//! The webhooks as the voice platform calls them: the server's router in-process,
+//! a temporary database, and a local stand-in for Perplexity.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use async_trait::async_trait;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use chrono::{DateTime, Datelike, Utc, Weekday};
+use chrono_tz::Europe::Helsinki;
+use dumbassistant::answers::APOLOGY;
+use dumbassistant::config::{Config, DeliveryChannel};
+use dumbassistant::db;
+use dumbassistant::delivery::DeliveryError;
+use dumbassistant::server::{self, AppState};
+use dumbassistant::sms::SmsSender;
+use dumbassistant::users;
+use dumbassistant::webhook_auth::{WebhookAuth, SECRET_HEADER};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use tempfile::TempDir;
+use tower::ServiceExt;
+use wiremock::matchers::{body_partial_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const CALLER: &str = "+358401234567";
+
+/// Texts kept instead of sent.
+#[derive(Default)]
+struct Outbox(Mutex<Vec<(String, String)>>);
+
+#[async_trait]
+impl SmsSender for Outbox {
+    async fn send(&self, to: &str, body: &str) -> Result<(), DeliveryError> {
+        self.0.lock().unwrap().push((to.to_string(), body.to_string()));
+        Ok(())
+    }
+}
+
+struct Server {
+    app: Router,
+    perplexity: MockServer,
+    outbox: Arc<Outbox>,
+    config: Arc<Config>,
+    _dir: TempDir,
+}
+
+async fn server_with(configure: impl FnOnce(&mut Config)) -> Server {
+    let dir = tempfile::tempdir().unwrap();
+    let perplexity = MockServer::start().await;
+
+    let mut config = Config::default();
+    config.database.url = dir.path().join("webhooks.db").to_str().unwrap().to_string();
+    config.server.public_url = Some("https://assistant.example.com".to_string());
+    config.reminders.delivery = DeliveryChannel::Log;
+    config.perplexity.api_key = Some("test-key".to_string());
+    config.perplexity.base_url = perplexity.uri();
+    config.answers.retries = 0;
+    configure(&mut config);
+
+    let pool = db::pool(&config.database.url, 4).unwrap();
+    db::run_pending_migrations(&mut pool.get().unwrap()).unwrap();
+    let config = Arc::new(config);
+    let outbox = Arc::new(Outbox::default());
+    let mut state = AppState::from_config(config.clone(), pool);
+    state.sms = outbox.clone();
+
+    Server { app: server::router(state), perplexity, outbox, config, _dir: dir }
+}
+
+async fn server() -> Server {
+    server_with(|_| {}).await
+}
+
+/// A tool-calls webhook from `number`, or from a web call without one, with the
+/// calls numbered `call_0`, `call_1`, … in order.
+fn tool_calls(number: Option<&str>, calls: &[(&str, Value)]) -> Value {
+    let calls: Vec<Value> = calls
+        .iter()
+        .enumerate()
+        .map(|(i, (name, arguments))| {
+            json!({
+                "id": format!("call_{}", i),
+                "type": "function",
+                "function": { "name": name, "arguments": arguments }
+            })
+        })
+        .collect();
+    json!({
+        "message": {
+            "type": "tool-calls",
+            "timestamp": Utc::now().timestamp_millis(),
+            "toolCalls": calls,
+            "call": { "id": "vapi-call-1", "customer": number.map(|number| json!({ "number": number })) }
+        }
+    })
+}
+
+impl Server {
+    async fn send(&self, request: Request<Body>) -> (StatusCode, Value) {
+        let response = self.app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body = serde_json::from_slice(&bytes).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()));
+        (status, body)
+    }
+
+    async fn post(&self, uri: &str, payload: &Value) -> (StatusCode, Value) {
+        let body = payload.to_string();
+        let mut request = Request::post(uri).header("content-type", "application/json");
+        for (name, value) in WebhookAuth::from_config(&self.config).credentials(body.as_bytes(), Utc::now().timestamp()) {
+            request = request.header(name, value);
+        }
+        self.send(request.body(Body::from(body)).unwrap()).await
+    }
+
+    /// Makes the calls as `CALLER` and returns their results, checking they come
+    /// back in order and without errors.
+    async fn call(&self, calls: &[(&str, Value)]) -> Vec<Value> {
+        let (status, body) = self.post("/tool-call", &tool_calls(Some(CALLER), calls)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let results = body["results"].as_array().unwrap().clone();
+        assert_eq!(results.len(), calls.len(), "{}", body);
+        for (i, result) in results.iter().enumerate() {
+            assert_eq!(result["toolCallId"], format!("call_{}", i));
+            assert!(result.get("error").is_none(), "{}", result);
+        }
+        results.into_iter().map(|result| result["result"].clone()).collect()
+    }
+
+    async fn call_one(&self, name: &str, arguments: Value) -> Value {
+        self.call(&[(name, arguments)]).await.remove(0)
+    }
+}
+
+fn completion(content: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "id": "cmpl-1",
+        "model": "llama-3.1-sonar-small-128k-online",
+        "citations": ["https://en.wikipedia.org/wiki/Helsinki"],
+        "choices": [{ "index": 0, "message": { "role": "assistant", "content": content } }],
+        "usage": { "prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15 }
+    }))
+}
+
+#[tokio::test]
+async fn manages_reminders_through_every_reminder_tool() {
+    let server = server().await;
+
+    let stored = server
+        .call_one("StoreUserReminder", json!({ "message": "take pills", "remind_at": "in 2 hours" }))
+        .await;
+    assert_eq!(stored["message"], "take pills");
+    let id = stored["id"].as_str().unwrap().to_string();
+
+    let listing = server.call_one("GetUserReminders", json!({})).await;
+    assert_eq!(listing.as_array().unwrap().len(), 1);
+    assert_eq!(listing[0]["id"], id.as_str());
+    assert_eq!(listing[0]["timezone"], "Europe/Helsinki");
+
+    let updated = server
+        .call_one("UpdateReminder", json!({ "id": id, "message": "take the blue pills" }))
+        .await;
+    assert_eq!(updated["message"], "take the blue pills");
+    assert_eq!(updated["remind_at"], stored["remind_at"]);
+
+    let snoozed = server.call_one("SnoozeReminder", json!({ "id": id, "minutes": 10 })).await;
+    assert_ne!(snoozed["remind_at"], stored["remind_at"]);
+
+    let deleted = server.call_one("DeleteReminder", json!({ "id": id })).await;
+    assert_eq!(deleted["id"], id.as_str());
+    let missing = server.call_one("DeleteReminder", json!({ "id": id })).await;
+    assert!(missing.as_str().unwrap().starts_with("I couldn't find that reminder"));
+
+    server
+        .call(&[
+            ("StoreUserReminder", json!({ "message": "one", "remind_at": "tomorrow at 9" })),
+            ("StoreUserReminder", json!({ "message": "two", "remind_at": "tomorrow at 10" })),
+        ])
+        .await;
+    assert_eq!(server.call_one("DeleteAllReminders", json!({})).await, json!([]));
+    assert_eq!(server.call_one("GetUserReminders", json!({})).await, json!([]));
+}
+
+#[tokio::test]
+async fn keeps_repeating_reminders_with_their_rule() {
+    let server = server().await;
+    let stored = server
+        .call_one(
+            "StoreUserReminder",
+            json!({
+                "message": "water the plants",
+                "remind_at": "tomorrow at 9",
+                "recurrence": { "frequency": "weekly", "by_weekday": ["mon"] }
+            }),
+        )
+        .await;
+    assert!(stored["recurrence"].as_str().unwrap().starts_with("FREQ=WEEKLY;BYDAY=MO;BYHOUR=9;BYMINUTE=0"), "{}", stored);
+    // whatever day tomorrow is, the first one goes off on a Monday
+    let first = DateTime::parse_from_rfc3339(stored["remind_at"].as_str().unwrap()).unwrap();
+    assert_eq!(first.with_timezone(&Helsinki).weekday(), Weekday::Mon, "{}", stored);
+
+    let listing = server.call_one("GetUserReminders", json!({})).await;
+    assert!(!listing[0]["upcoming"].as_array().unwrap().is_empty(), "{}", listing);
+}
+
+#[tokio::test]
+async fn reads_times_in_the_callers_timezone() {
+    let server = server().await;
+    let set = server.call_one("SetUserTimezone", json!({ "timezone": "America/New_York" })).await;
+    assert_eq!(set, "Got it, I'll use America/New_York time from now on.");
+    let unknown = server.call_one("SetUserTimezone", json!({ "timezone": "Middle Earth" })).await;
+    assert!(unknown.as_str().unwrap().starts_with("I don't know the timezone"));
+
+    server
+        .call_one("StoreUserReminder", json!({ "message": "call mom", "remind_at": "2099-01-01 09:00" }))
+        .await;
+    let listing = server.call_one("GetUserReminders", json!({})).await;
+    assert_eq!(listing[0]["timezone"], "America/New_York");
+    assert_eq!(listing[0]["local_time"], "2099-01-01T09:00:00-05:00");
+    assert_eq!(listing[0]["remind_at"], "2099-01-01T14:00:00Z");
+}
+
+#[tokio::test]
+async fn asks_the_questions_it_has_not_answered_yet() {
+    let server = server().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(header("authorization", "Bearer test-key"))
+        .and(body_partial_json(json!({ "messages": [{ "role": "system" }, { "role": "user", "content": "capital of Finland?" }] })))
+        .respond_with(completion("The capital is **Helsinki**[1]."))
+        .expect(1)
+        .mount(&server.perplexity)
+        .await;
+
+    let answer = server.call_one("AskQuestion", json!({ "message": "capital of Finland?" })).await;
+    assert_eq!(answer, "The capital is Helsinki.");
+    // the same question again comes from the cache, under the tool's old name too
+    let again = server.call_one("AskPerplexity", json!({ "question": "capital of Finland?" })).await;
+    assert_eq!(again, "The capital is Helsinki.");
+    assert!(server.outbox.0.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn asks_again_after_the_caller_switches_provider() {
+    let server = server_with(|config| config.openai.base_url = Some(config.perplexity.base_url.clone())).await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(completion("Helsinki."))
+        .expect(2)
+        .mount(&server.perplexity)
+        .await;
+
+    assert_eq!(server.call_one("AskQuestion", json!({ "message": "capital of Finland?" })).await, "Helsinki.");
+    let pool = db::pool(&server.config.database.url, 1).unwrap();
+    let mut conn = pool.get().unwrap();
+    let user = users::find_by_phone(&mut conn, CALLER).unwrap().unwrap();
+    users::set_answer_provider(&mut conn, &user, Some("openai")).unwrap();
+
+    // the other provider's answer isn't in the cache yet
+    assert_eq!(server.call_one("AskQuestion", json!({ "message": "capital of Finland?" })).await, "Helsinki.");
+}
+
+#[tokio::test]
+async fn texts_long_answers_with_their_sources() {
+    let server = server().await;
+    let long = "Helsinki has been the capital since 1812. ".repeat(10);
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(completion(&long))
+        .mount(&server.perplexity)
+        .await;
+
+    let answer = server.call_one("AskQuestion", json!({ "message": "history of Helsinki?" })).await;
+    assert_eq!(answer, long.trim());
+
+    // the text goes out in the background
+    for _ in 0..50 {
+        if !server.outbox.0.lock().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let texts = server.outbox.0.lock().unwrap().clone();
+    assert_eq!(texts.len(), 1);
+    assert_eq!(texts[0].0, CALLER);
+    assert!(texts[0].1.ends_with("Sources:\n[1] https://en.wikipedia.org/wiki/Helsinki"), "{}", texts[0].1);
+}
+
+#[tokio::test]
+async fn apologises_when_perplexity_is_down() {
+    let server = server().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(503).set_body_string("overloaded"))
+        .expect(1)
+        .mount(&server.perplexity)
+        .await;
+
+    let answer = server.call_one("AskQuestion", json!({ "message": "weather tomorrow?" })).await;
+    assert_eq!(answer, APOLOGY);
+}
+
+#[tokio::test]
+async fn apologises_before_the_deadline_when_every_provider_hangs() {
+    // each attempt fits, but falling back from one hung provider to the next doesn't
+    let server = server_with(|config| {
+        config.server.tool_deadline_ms = 1_500;
+        config.answers.timeout_ms = 800;
+        config.openai.base_url = Some(config.perplexity.base_url.clone());
+    })
+    .await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(completion("too late").set_delay(Duration::from_secs(5)))
+        .mount(&server.perplexity)
+        .await;
+
+    let answer = server.call_one("AskQuestion", json!({ "message": "weather tomorrow?" })).await;
+    assert_eq!(answer, APOLOGY);
+}
+
+#[tokio::test]
+async fn answers_every_call_in_a_batch_in_order() {
+    let server = server().await;
+    let results = server
+        .call(&[
+            ("StoreUserReminder", json!({ "message": "dentist", "remind_at": "next friday at 14" })),
+            ("LaunchRockets", json!({})),
+            ("StoreUserReminder", json!({ "message": "no time" })),
+            ("StoreUserReminder", json!({ "message": "too late", "remind_at": "2001-01-01T00:00:00Z" })),
+            ("GetUserReminders", "{}".into()),
+        ])
+        .await;
+
+    assert_eq!(results[0]["message"], "dentist");
+    assert_eq!(results[1], "Unknown function call");
+    assert!(results[2].as_str().unwrap().contains("remind_at"), "{}", results[2]);
+    assert!(results[2].as_str().unwrap().ends_with("Please call StoreUserReminder again with that fixed."));
+    assert_eq!(results[3], "That time has already passed. When should I remind you instead?");
+    // the listing waits for the reminders stored before it
+    let listed: Vec<&str> = results[4].as_array().unwrap().iter().map(|reminder| reminder["message"].as_str().unwrap()).collect();
+    assert_eq!(listed, ["dentist"], "{}", results[4]);
+}
+
+#[tokio::test]
+async fn web_callers_cannot_keep_reminders() {
+    let server = server().await;
+    let payload = tool_calls(None, &[("StoreUserReminder", json!({ "message": "x", "remind_at": "in 1 hour" }))]);
+    let (status, body) = server.post("/tool-call", &payload).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["results"][0]["result"], "I can only keep reminders for you when you call from your phone.");
+}
+
+#[tokio::test]
+async fn keeps_each_callers_reminders_to_themselves() {
+    let server = server().await;
+    let stored = server
+        .call_one("StoreUserReminder", json!({ "message": "secret", "remind_at": "in 1 hour" }))
+        .await;
+
+    let other = "+358509876543";
+    let payload = tool_calls(
+        Some(other),
+        &[("GetUserReminders", json!({})), ("DeleteReminder", json!({ "id": stored["id"] }))],
+    );
+    let (_, body) = server.post("/tool-call", &payload).await;
+    assert_eq!(body["results"][0]["result"], json!([]));
+    assert!(body["results"][1]["result"].as_str().unwrap().starts_with("I couldn't find that reminder"));
+    assert_eq!(server.call_one("GetUserReminders", json!({})).await.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn turns_away_unauthenticated_webhooks() {
+    let server = server_with(|config| config.webhook.secret = Some("hunter2".to_string())).await;
+    let payload = tool_calls(Some(CALLER), &[("GetUserReminders", json!({}))]);
+    let request = |secret: Option<&str>| {
+        let mut request = Request::post("/tool-call").header("content-type", "application/json");
+        if let Some(secret) = secret {
+            request = request.header(SECRET_HEADER, secret);
+        }
+        request.body(Body::from(payload.to_string())).unwrap()
+    };
+
+    assert_eq!(server.send(request(None)).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(server.send(request(Some("hunter3"))).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(server.send(request(Some("hunter2"))).await.0, StatusCode::OK);
+    // the definitions aren't a webhook
+    let (status, _) = server.send(Request::get("/tools").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn refuses_payloads_that_are_not_tool_calls() {
+    let server = server().await;
+    let (status, _) = server.post("/tool-call", &json!({ "message": { "type": "tool-calls" } })).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let request = Request::post("/tool-call").header("content-type", "application/json").body(Body::from("{")).unwrap();
+    assert_eq!(server.send(request).await.0, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn acknowledges_call_events() {
+    let server = server().await;
+    let event = json!({
+        "message": {
+            "type": "end-of-call-report",
+            "call": { "id": "vapi-call-1" },
+            "endedReason": "customer-did-not-answer"
+        }
+    });
+    assert_eq!(server.post("/call-events", &event).await.0, StatusCode::OK);
+
+    let ignored = json!({ "message": { "type": "speech-update" } });
+    assert_eq!(server.post("/call-events", &ignored).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn describes_the_enabled_tools() {
+    let server = server_with(|config| config.tools.disabled = vec!["AskQuestion".to_string()]).await;
+    let (status, definitions) = server.send(Request::get("/tools").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let names: Vec<&str> = definitions
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|definition| definition["function"]["name"].as_str().unwrap())
+        .collect();
+    assert!(names.contains(&"StoreUserReminder"));
+    assert!(!names.contains(&"AskQuestion"));
+    assert_eq!(definitions[0]["server"]["url"], "https://assistant.example.com/tool-call");
+
+    // and a disabled tool is treated as unknown
+    assert_eq!(server.call_one("AskQuestion", json!({ "message": "hi?" })).await, "Unknown function call");
+}